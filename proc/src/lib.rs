@@ -73,6 +73,23 @@ pub fn bundle(input: TokenStream) -> TokenStream {
                     }
                 }
             };
+            // 2.5 components_names
+            let components_names = struct_.fields.clone().into_iter().map(|field| {
+                let ty = field.ty;
+                quote! {
+                    ::std::any::type_name::<#ty>()
+                }
+            });
+            let components_names = quote! {
+                fn components_names() -> &'static [&'static str]{
+                    static COMPONNETS_NAMES :
+                        ::std::sync::OnceLock<Vec<&'static str>>
+                        = ::std::sync::OnceLock::new();
+                    COMPONNETS_NAMES.get_or_init(||{
+                        vec![#(#components_names,)*]
+                    })
+                }
+            };
             // 3 type_name
             let type_name = quote! {
                 fn type_name() -> &'static str{
@@ -106,6 +123,7 @@ pub fn bundle(input: TokenStream) -> TokenStream {
                 impl ::trecs::bundle::Bundle for #struct_name{
                     #destory
                     #components_ids
+                    #components_names
                     #drop
                     #type_name
                     #type_id_
@@ -2,8 +2,9 @@ use std::time::{Duration, Instant};
 
 use trecs::bundle::{Bundle, Component};
 
+use trecs::app::AppExit;
 use trecs::tools::Command;
-use trecs::world::Query;
+use trecs::world::{Query, Res};
 use trecs::World;
 
 #[derive(Bundle)]
@@ -39,9 +40,17 @@ fn main() {
         while instant.elapsed() < Duration::from_secs_f64(1.0 / 2.0) {}
     }
 
-    // 计时器 五秒后开始返回true
-    let start = Instant::now();
-    let delay = || start.elapsed() > Duration::from_secs(5);
+    // 五秒后请求退出,会在这次循环结束后生效
+    fn exit_after_5s(mut start: Res<Instant>, mut exit: Res<AppExit>) {
+        if start.get_or_init(Instant::now).elapsed() > Duration::from_secs(5) {
+            exit.get_or_init(|| AppExit);
+        }
+    }
+
+    // 退出前执行一次
+    fn goodbye() {
+        println!("Goodbye from system `goodbye`")
+    }
 
     // 创建世界,创建几个entity
     let mut world = World::new();
@@ -67,5 +76,7 @@ fn main() {
         .add_system(hello_world)
         .add_system(hello_world_from_cs)
         .add_system(twice_pre_s)
-        .run_until(delay);
+        .add_system(exit_after_5s)
+        .add_shutdown_system(goodbye)
+        .run();
 }
@@ -0,0 +1,334 @@
+use std::collections::HashSet;
+
+#[cfg(feature = "async")]
+use std::future::Future;
+
+use crate::{
+    error::TrecsError,
+    system::{InnerSystem, SystemOutput},
+    tools::ResManager,
+    World,
+};
+
+/// 一组可以一起添加到[App]中的[System]和资源
+///
+/// ```ignore
+/// struct PhysicsPlugin;
+///
+/// impl Plugin for PhysicsPlugin {
+///     fn build(&self, app: &mut App) {
+///         app.insert_resource(Gravity(9.8)).add_system(apply_gravity);
+///     }
+/// }
+/// ```
+///
+/// [System]: crate::system::System
+pub trait Plugin: 'static {
+    fn build(&self, app: &mut App);
+
+    /// 用于检测重复添加的名字,默认是类型名
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// 是否只能添加一次
+    fn is_unique(&self) -> bool {
+        true
+    }
+}
+
+/// 请求退出的资源
+///
+/// [System]可以通过`Res<AppExit>`或者[Commands::exit]添加,
+/// [World::run]会在这次循环结束后执行shutdown_systems并返回
+///
+/// ```ignore
+/// fn quit(input: Res<Input>, mut exit: Res<AppExit>) {
+///     if input.get().unwrap().escape {
+///         exit.get_or_init(|| AppExit);
+///     }
+/// }
+/// ```
+///
+/// [System]: crate::system::System
+/// [Commands::exit]: crate::world::Commands::exit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AppExit;
+
+/// 由[App::run]调用的函数,决定[World]如何运行
+pub type Runner = Box<dyn FnOnce(App)>;
+
+/// 包装了[World]的应用,通过[Plugin]组织[System]和资源
+///
+/// ```ignore
+/// let mut app = App::new();
+/// app.add_plugin(PhysicsPlugin).add_system(render);
+/// app.run();
+/// ```
+///
+/// [System]: crate::system::System
+pub struct App {
+    world: World,
+    /// 已经添加的[Plugin]的名字
+    plugins: HashSet<&'static str>,
+    runner: Runner,
+}
+
+impl App {
+    /// 使用[run_loop]作为runner
+    pub fn new() -> Self {
+        Self {
+            world: World::new(),
+            plugins: HashSet::new(),
+            runner: Box::new(run_loop),
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// 添加一个[Plugin],会立即调用[Plugin::build]
+    ///
+    /// 重复添加[Plugin::is_unique]为true的[Plugin]时会panic,
+    /// 使用[App::try_add_plugin]获得[TrecsError]
+    pub fn add_plugin<P: Plugin>(&mut self, plugin: P) -> &mut Self {
+        if let Err(error) = self.try_add_plugin(plugin) {
+            panic!("{error}");
+        }
+        self
+    }
+
+    /// 添加一个[Plugin]
+    ///
+    /// 重复添加[Plugin::is_unique]为true的[Plugin]时返回[TrecsError],[Plugin]不会被添加
+    ///
+    /// [Plugin::build]完成之后才会记录为已经添加
+    pub fn try_add_plugin<P: Plugin>(&mut self, plugin: P) -> Result<&mut Self, TrecsError> {
+        let name = plugin.name();
+        if plugin.is_unique() && self.plugins.contains(name) {
+            return Err(TrecsError::DuplicatePlugin { plugin: name });
+        }
+        plugin.build(self);
+        self.plugins.insert(name);
+        Ok(self)
+    }
+
+    /// 是否已经添加过名字为name的[Plugin]
+    pub fn is_plugin_added(&self, name: &str) -> bool {
+        self.plugins.contains(name)
+    }
+
+    /// 和[World::add_system]相同
+    pub fn add_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        self.world.add_system(system);
+        self
+    }
+
+    /// 和[World::add_startup_system]相同
+    pub fn add_startup_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        self.world.add_startup_system(system);
+        self
+    }
+
+    /// 和[World::add_shutdown_system]相同
+    pub fn add_shutdown_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        self.world.add_shutdown_system(system);
+        self
+    }
+
+    /// 和[World::add_async_system]相同
+    #[cfg(feature = "async")]
+    pub fn add_async_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: Future + 'static,
+        <S::Out as Future>::Output: SystemOutput,
+    {
+        self.world.add_async_system(system);
+        self
+    }
+
+    /// 添加资源,已经存在时会被替换
+    pub fn insert_resource<T: 'static>(&mut self, resource: T) -> &mut Self {
+        let mut res = self.world.get_res::<T>();
+        match res.get_mut() {
+            Some(old) => *old = resource,
+            None => {
+                res.get_or_init(|| resource);
+            }
+        }
+        self
+    }
+
+    /// 设置runner,默认是[run_loop]
+    pub fn set_runner(&mut self, runner: impl FnOnce(App) + 'static) -> &mut Self {
+        self.runner = Box::new(runner);
+        self
+    }
+
+    /// 把[App]交给runner运行
+    pub fn run(mut self) {
+        let runner = std::mem::replace(&mut self.runner, Box::new(run_loop));
+        runner(self);
+    }
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 一直执行所有[System]直到出现[AppExit],和[World::run]相同
+///
+/// [System]: crate::system::System
+pub fn run_loop(mut app: App) {
+    app.world.run();
+}
+
+/// 执行一次startup_systems和所有[System]
+///
+/// [System]: crate::system::System
+pub fn run_once(mut app: App) {
+    app.world.startup().run_once();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::{App, Plugin};
+    use crate::{error::TrecsError, tools::ResManager, world::Res};
+
+    struct Counter;
+
+    fn count(mut counter: Res<usize>) {
+        *counter.get_mut().unwrap() += 1;
+    }
+
+    impl Plugin for Counter {
+        fn build(&self, app: &mut App) {
+            app.insert_resource(0usize).add_system(count);
+        }
+    }
+
+    /// 可以添加多次的Plugin
+    struct Repeat;
+
+    impl Plugin for Repeat {
+        fn build(&self, app: &mut App) {
+            app.add_system(count);
+        }
+
+        fn is_unique(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn plugins() {
+        let mut app = App::new();
+        app.add_plugin(Counter)
+            .add_plugin(Repeat)
+            .add_plugin(Repeat);
+        assert!(app.is_plugin_added(std::any::type_name::<Counter>()));
+        assert_eq!(
+            app.try_add_plugin(Counter).err(),
+            Some(TrecsError::DuplicatePlugin {
+                plugin: std::any::type_name::<Counter>()
+            })
+        );
+
+        app.insert_resource(10usize);
+        app.world_mut().run_once();
+        assert_eq!(app.world_mut().get_res::<usize>().get(), Some(&13));
+    }
+
+    #[test]
+    fn runner() {
+        let ran = Rc::new(Cell::new(0));
+        let mut app = App::new();
+        app.add_plugin(Counter).set_runner({
+            let ran = ran.clone();
+            move |mut app: App| {
+                app.world_mut().run_once();
+                app.world_mut().run_once();
+                ran.set(*app.world_mut().get_res::<usize>().get().unwrap());
+            }
+        });
+        app.run();
+        assert_eq!(ran.get(), 2);
+    }
+
+    #[test]
+    fn build_panic() {
+        struct Broken;
+
+        impl Plugin for Broken {
+            fn build(&self, _: &mut App) {
+                panic!("broken plugin");
+            }
+        }
+
+        let mut app = App::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            app.add_plugin(Broken);
+        }));
+        assert!(result.is_err());
+        assert!(!app.is_plugin_added(std::any::type_name::<Broken>()));
+    }
+
+    #[test]
+    fn exit() {
+        use super::AppExit;
+        use crate::world::Commands;
+
+        fn exit_at_3(counter: Res<usize>, mut commands: Commands) {
+            if counter.get() == Some(&3) {
+                commands.exit();
+            }
+        }
+
+        fn bye(counter: Res<usize>, exit: Res<AppExit>, mut log: Res<Vec<String>>) {
+            assert!(exit.get().is_some());
+            let counter = *counter.get().unwrap();
+            log.get_or_init(Vec::new);
+            log.get_mut().unwrap().push(format!("bye after {counter}"));
+        }
+
+        let mut app = App::new();
+        app.add_plugin(Counter)
+            .add_system(exit_at_3)
+            .add_shutdown_system(bye)
+            .set_runner(|mut app: App| {
+                // 请求退出后,这次循环中之后的System照常执行
+                app.add_system(count);
+                app.world_mut().run();
+                let world = app.world_mut();
+                assert_eq!(world.get_res::<usize>().get(), Some(&4));
+                assert_eq!(
+                    world.get_res::<Vec<String>>().get(),
+                    Some(&vec!["bye after 4".to_string()])
+                );
+                assert!(!world.exit_requested());
+            });
+        app.run();
+    }
+}
@@ -1,4 +1,8 @@
-use std::any::{Any, TypeId};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
 
 use super::Bundle;
 /// 最基本的构成单元
@@ -14,9 +18,25 @@ pub trait Component: Any {
 
 pub type Components = Vec<Box<dyn Any>>;
 
+/// 缓存[Bundle::components_names]的结果
+///
+/// 和components_ids一样,不同的[Bundle]会共用同一个静态变量,所以用上哈希表
+pub(super) fn cached_names(
+    bundle_id: TypeId,
+    init: impl FnOnce() -> Vec<&'static str>,
+) -> &'static [&'static str] {
+    static COMPONENT_NAMES: OnceLock<Mutex<HashMap<TypeId, &'static [&'static str]>>> =
+        OnceLock::new();
+    let mut names = COMPONENT_NAMES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap();
+    names.entry(bundle_id).or_insert_with(|| Vec::leak(init()))
+}
+
 // #[rustfmt::skip]
 mod __impl {
-    use super::{Bundle, Component, Components};
+    use super::{cached_names, Bundle, Component, Components};
     use std::{
         any::{type_name, Any, TypeId},
         cell::OnceCell,
@@ -70,6 +90,10 @@ mod __impl {
                     }
                 }
 
+                fn components_names() -> &'static [&'static str] {
+                    cached_names(Self::type_id_(), || vec![$($t::type_name(),)*])
+                }
+
                 fn drop(cs : Components){
                     // 迭代 && 颠倒
                     let mut iter = cs.into_iter().rev();
@@ -116,6 +140,10 @@ mod __impl {
             }
         }
 
+        fn components_names() -> &'static [&'static str] {
+            cached_names(Self::type_id_(), || vec![Self::type_name()])
+        }
+
         fn drop(mut cs: Components) {
             drop(cs.pop().unwrap().downcast::<Self>());
         }
@@ -0,0 +1,281 @@
+use std::{
+    alloc::{alloc, dealloc, handle_alloc_error, Layout},
+    any::TypeId,
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Debug,
+    ptr::NonNull,
+};
+
+use super::Component;
+use crate::error::TrecsError;
+
+/// 一种[Component]的标识
+///
+/// + 静态的[Component]由[TypeId]标识
+/// + 运行时注册的[Component]由注册时分配的下标标识
+///
+/// [Component]:crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ComponentId {
+    Static(TypeId),
+    Dynamic(usize),
+}
+
+impl ComponentId {
+    /// 静态的[Component]对应的[ComponentId]
+    pub fn of<C: Component>() -> Self {
+        Self::Static(C::type_id_())
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        matches!(self, Self::Dynamic(..))
+    }
+}
+
+impl From<TypeId> for ComponentId {
+    fn from(type_id: TypeId) -> Self {
+        Self::Static(type_id)
+    }
+}
+
+/// 运行时[Component]的描述
+///
+/// 没有对应的Rust类型,只有名字,内存布局和用来Drop的函数
+///
+/// [Component]:crate
+#[derive(Debug, Clone)]
+pub struct ComponentDescriptor {
+    pub(crate) name: Cow<'static, str>,
+    pub(crate) layout: Layout,
+    pub(crate) drop: Option<unsafe fn(*mut u8)>,
+}
+
+impl ComponentDescriptor {
+    /// 不需要Drop的[ComponentDescriptor]
+    pub fn new(name: impl Into<Cow<'static, str>>, layout: Layout) -> Self {
+        Self {
+            name: name.into(),
+            layout,
+            drop: None,
+        }
+    }
+
+    /// 和Rust类型T布局相同的[ComponentDescriptor]
+    ///
+    /// 只使用T的布局,不会使用T的[Drop],
+    /// 因为通过[World::insert_bytes]写入的数据不一定是合法的T
+    ///
+    /// 需要Drop时使用[ComponentDescriptor::with_drop]
+    ///
+    /// [World::insert_bytes]:crate::World::insert_bytes
+    pub fn of<T: 'static>(name: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(name, Layout::new::<T>())
+    }
+
+    /// 设置Drop时调用的函数
+    ///
+    /// # Safety
+    ///
+    /// drop会在组件被删除时,以指向组件的指针调用
+    ///
+    /// 必须能处理所有通过[World::insert_bytes]和[World::get_bytes_mut]写入的数据
+    ///
+    /// [World::insert_bytes]:crate::World::insert_bytes
+    /// [World::get_bytes_mut]:crate::World::get_bytes_mut
+    pub unsafe fn with_drop(mut self, drop: unsafe fn(*mut u8)) -> Self {
+        self.drop = Some(drop);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+}
+
+/// 存放在[Components]中的运行时[Component]
+///
+/// [Components]:crate
+/// [Component]:crate
+pub(crate) struct RawComponent {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    drop: Option<unsafe fn(*mut u8)>,
+}
+
+impl RawComponent {
+    /// 按照[ComponentDescriptor]分配内存,并复制bytes
+    ///
+    /// bytes的长度必须和布局的大小相同
+    pub fn new(descriptor: &ComponentDescriptor, bytes: &[u8]) -> Self {
+        let layout = descriptor.layout;
+        assert_eq!(bytes.len(), layout.size());
+        let ptr = if layout.size() == 0 {
+            // 零大小的类型不需要分配,只需要对齐
+            NonNull::new(layout.align() as *mut u8).unwrap()
+        } else {
+            let ptr = unsafe { alloc(layout) };
+            NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(layout))
+        };
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.as_ptr(), bytes.len()) };
+        Self {
+            ptr,
+            layout,
+            drop: descriptor.drop,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for RawComponent {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(drop) = self.drop {
+                drop(self.ptr.as_ptr());
+            }
+            if self.layout.size() != 0 {
+                dealloc(self.ptr.as_ptr(), self.layout);
+            }
+        }
+    }
+}
+
+/// 所有运行时注册的[ComponentDescriptor]
+#[derive(Default)]
+pub(crate) struct DynamicComponents {
+    descriptors: Vec<ComponentDescriptor>,
+    names: HashMap<&'static str, usize>,
+}
+
+impl DynamicComponents {
+    /// 注册[ComponentDescriptor],名字相同时返回已经注册的[ComponentId]
+    ///
+    /// 名字相同但是布局或者Drop函数不同时返回[TrecsError]
+    pub fn register(&mut self, descriptor: ComponentDescriptor) -> Result<ComponentId, TrecsError> {
+        if let Some((&name, &index)) = self.names.get_key_value(&*descriptor.name) {
+            let exist = &self.descriptors[index];
+            let same_drop = match (exist.drop, descriptor.drop) {
+                (Some(a), Some(b)) => std::ptr::fn_addr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            };
+            if exist.layout != descriptor.layout || !same_drop {
+                return Err(TrecsError::ComponentMismatch { component: name });
+            }
+            return Ok(ComponentId::Dynamic(index));
+        }
+
+        // 运行时注册的组件数量是有限的,所以直接泄露掉
+        let name: &'static str = match descriptor.name {
+            Cow::Borrowed(name) => name,
+            Cow::Owned(name) => name.leak(),
+        };
+        self.names.insert(name, self.descriptors.len());
+        self.descriptors.push(ComponentDescriptor {
+            name: Cow::Borrowed(name),
+            ..descriptor
+        });
+        Ok(ComponentId::Dynamic(self.descriptors.len() - 1))
+    }
+
+    pub fn get(&self, id: ComponentId) -> Option<&ComponentDescriptor> {
+        match id {
+            ComponentId::Dynamic(index) => self.descriptors.get(index),
+            ComponentId::Static(..) => None,
+        }
+    }
+
+    /// 运行时注册的[Component]的名字
+    ///
+    /// [Component]:crate
+    pub fn name(&self, id: ComponentId) -> Option<&'static str> {
+        // 注册时名字已经被泄露,所以一定是Borrowed
+        match self.get(id)?.name {
+            Cow::Borrowed(name) => Some(name),
+            Cow::Owned(..) => None,
+        }
+    }
+
+    pub fn id(&self, name: &str) -> Option<ComponentId> {
+        self.names.get(name).copied().map(ComponentId::Dynamic)
+    }
+}
+
+impl Debug for DynamicComponents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.descriptors.iter().map(|d| d.name()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    #[test]
+    fn raw_component() {
+        let mut components = DynamicComponents::default();
+        let hp = components
+            .register(ComponentDescriptor::of::<u32>("hp"))
+            .unwrap();
+        assert_eq!(
+            components.register(ComponentDescriptor::of::<u32>("hp".to_string())),
+            Ok(hp)
+        );
+        // 名字相同,布局不同
+        assert_eq!(
+            components.register(ComponentDescriptor::of::<u64>("hp")),
+            Err(TrecsError::ComponentMismatch { component: "hp" })
+        );
+        assert_eq!(components.id("hp"), Some(hp));
+
+        let descriptor = components.get(hp).unwrap();
+        let mut raw = RawComponent::new(descriptor, &7u32.to_ne_bytes());
+        raw.bytes_mut().copy_from_slice(&8u32.to_ne_bytes());
+        assert_eq!(raw.bytes(), 8u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn drop() {
+        let counter = Rc::new(Cell::new(0));
+        struct Counter(Rc<Cell<i32>>);
+        impl Drop for Counter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        unsafe fn drop_counter(ptr: *mut u8) {
+            std::ptr::drop_in_place(ptr as *mut Counter)
+        }
+
+        // 只有布局,不会Drop
+        let descriptor = ComponentDescriptor::of::<Counter>("counter");
+        assert!(descriptor.drop.is_none());
+        // 写入的一定是Counter,所以可以使用Counter的Drop
+        let descriptor = unsafe { descriptor.with_drop(drop_counter) };
+        let value = std::mem::ManuallyDrop::new(Counter(counter.clone()));
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &*value as *const Counter as *const u8,
+                std::mem::size_of::<Counter>(),
+            )
+        };
+        std::mem::drop(RawComponent::new(&descriptor, bytes));
+        assert_eq!(counter.get(), 1);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
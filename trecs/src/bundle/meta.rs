@@ -1,12 +1,21 @@
 use std::{
     any::{type_name, TypeId},
+    cell::RefCell,
     collections::HashMap,
     fmt::Debug,
 };
 
-use crate::tools::{MappingTable, WorldFetch, WorldFilter};
+use crate::{
+    tools::{MappingTable, WorldFetch, WorldFilter},
+    world::Access,
+};
+
+use super::{Bundle, ComponentId, Components};
 
-use super::{Bundle, Components};
+/// [QueryBuilder]中所有的要求
+///
+/// [QueryBuilder]: crate::world::QueryBuilder
+type Terms = Vec<(ComponentId, Access)>;
 
 /// 一个[Bundle]的信息
 ///
@@ -14,18 +23,34 @@ use super::{Bundle, Components};
 ///
 /// 比如(123,&&str) 就是一个Bundle
 pub struct BundleMeta {
-    /// [Bundle]的typeid
-    pub bundle_id: TypeId,
     /// [Bundle]的所有Componenets的id
     pub components_ids: &'static [TypeId],
+    /// [Bundle]的所有Componenets的类型名,和components_ids一一对应
+    pub components_names: &'static [&'static str],
+    /// 运行时注册的Componenets的id
+    ///
+    /// 在[Components]中排在所有静态的Componenets之后
+    pub dynamic_ids: &'static [ComponentId],
     /// [Bundle]对于每种[WorldFilter]的结果
     ///
     /// 避免每次都重新计算
-    pub filter_cache: HashMap<TypeId, bool>,
+    ///
+    /// [QueryState]只持有[World]的不可变引用,所以放在[RefCell]中
+    ///
+    /// [QueryState]: crate::world::QueryState
+    /// [World]: crate::World
+    pub filter_cache: RefCell<HashMap<TypeId, bool>>,
     /// 每种[WorldFetch]对于此[Bundle]的[MappingTable]
     ///
     /// 避免每次都重新计算
-    pub fetch_cache: HashMap<TypeId, MappingTable>,
+    pub fetch_cache: RefCell<HashMap<TypeId, MappingTable>>,
+    /// 每种[QueryBuilder]的要求对于此[Bundle]的结果
+    ///
+    /// [DynamicQuery]只持有[World]的不可变引用,所以放在[RefCell]中
+    ///
+    /// [QueryBuilder]: crate::world::QueryBuilder
+    /// [DynamicQuery]: crate::world::DynamicQuery
+    pub dynamic_cache: RefCell<HashMap<Terms, Option<Vec<usize>>>>,
     /// [World]中所有存放此类[Bundle]的[Chunk]的下标
     pub chunks: Vec<usize>,
 
@@ -41,34 +66,117 @@ impl BundleMeta {
     pub fn new<B: Bundle>() -> Self {
         let droper = |cs: Components| B::drop(cs);
         Self {
-            bundle_id: B::type_id_(),
             components_ids: B::components_ids(),
+            components_names: B::components_names(),
+            dynamic_ids: &[],
             filter_cache: Default::default(),
             fetch_cache: Default::default(),
+            dynamic_cache: Default::default(),
             chunks: vec![],
             bundle_info: (type_name::<B>(), B::type_name()),
             droper: Box::new(droper),
         }
     }
 
-    pub fn filter<F: WorldFilter>(&mut self) -> bool {
-        let filter_id = TypeId::of::<F>();
+    /// 由若干[Component]临时组合出的[BundleMeta]
+    ///
+    /// 在向已经存在的[Entity]添加或者删除[Component]时产生,
+    /// 并没有一个对应的[Bundle]类型
+    ///
+    /// [Component]:crate
+    pub fn dynamic(
+        components_ids: &'static [TypeId],
+        components_names: &'static [&'static str],
+        dynamic_ids: &'static [ComponentId],
+    ) -> Self {
+        // Box<dyn Any>会正常Drop其中的值
+        let droper = |cs: Components| drop(cs);
+        Self {
+            components_ids,
+            components_names,
+            dynamic_ids,
+            filter_cache: Default::default(),
+            fetch_cache: Default::default(),
+            dynamic_cache: Default::default(),
+            chunks: vec![],
+            bundle_info: ("dynamic", "dynamic"),
+            droper: Box::new(droper),
+        }
+    }
 
-        self.filter_cache
-            .entry(filter_id)
-            .or_insert_with(|| F::filter(self.components_ids))
-            .to_owned()
+    /// [ComponentId]对应的[Component]在[Components]中的下标
+    ///
+    /// [Component]:crate
+    pub fn position(&self, id: ComponentId) -> Option<usize> {
+        match id {
+            ComponentId::Static(type_id) => {
+                self.components_ids.iter().position(|id| *id == type_id)
+            }
+            ComponentId::Dynamic(..) => self
+                .dynamic_ids
+                .iter()
+                .position(|dynamic| *dynamic == id)
+                .map(|idx| idx + self.components_ids.len()),
+        }
     }
 
-    pub fn fetch<F: WorldFetch>(&mut self) -> Option<&MappingTable> {
+    pub fn filter<F: WorldFilter>(&self) -> bool {
+        let filter_id = TypeId::of::<F>();
+        if let Some(result) = self.filter_cache.borrow().get(&filter_id) {
+            return *result;
+        }
+        let result = F::filter(self.components_ids);
+        self.filter_cache.borrow_mut().insert(filter_id, result);
+        result
+    }
+
+    pub fn fetch<F: WorldFetch>(&self) -> Option<MappingTable> {
         let fetch_id = F::Bundle::type_id_();
-        if let std::collections::hash_map::Entry::Vacant(e) = self.fetch_cache.entry(fetch_id) {
-            let mapping_table = F::contain(&mut self.components_ids.to_vec());
-            if let Some(mapping_table) = mapping_table {
-                e.insert(mapping_table);
+        if let Some(mapping_table) = self.fetch_cache.borrow().get(&fetch_id) {
+            return Some(mapping_table.clone());
+        }
+        let mapping_table = F::contain(&mut self.components_ids.to_vec())?;
+        self.fetch_cache
+            .borrow_mut()
+            .insert(fetch_id, mapping_table.clone());
+        Some(mapping_table)
+    }
+}
+
+impl BundleMeta {
+    /// [QueryBuilder]的要求对于此[Bundle]的结果
+    ///
+    /// 满足要求时返回读写的[Component]在[Components]中的下标
+    ///
+    /// [QueryBuilder]: crate::world::QueryBuilder
+    /// [Component]:crate
+    pub fn dynamic_fetch(&self, terms: &[(ComponentId, Access)]) -> Option<Vec<usize>> {
+        if let Some(mapping) = self.dynamic_cache.borrow().get(terms) {
+            return mapping.clone();
+        }
+        let mapping = self.match_terms(terms);
+        self.dynamic_cache
+            .borrow_mut()
+            .insert(terms.to_vec(), mapping.clone());
+        mapping
+    }
+
+    fn match_terms(&self, terms: &[(ComponentId, Access)]) -> Option<Vec<usize>> {
+        let mut mapping = vec![];
+        for (id, access) in terms {
+            match access {
+                Access::Read | Access::Write => mapping.push(self.position(*id)?),
+                Access::With => {
+                    self.position(*id)?;
+                }
+                Access::Without => {
+                    if self.position(*id).is_some() {
+                        return None;
+                    }
+                }
             }
         }
-        self.fetch_cache.get(&fetch_id)
+        Some(mapping)
     }
 }
 
@@ -76,7 +184,8 @@ impl Debug for BundleMeta {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("BundleMeta")
             .field("bundle_id", &self.bundle_info.0)
-            .field("components_ids", &self.bundle_info.1)
+            .field("components_ids", &self.components_names)
+            .field("dynamic_ids", &self.dynamic_ids)
             .field("filter_cache", &self.filter_cache)
             .field("fetch_cache", &self.fetch_cache)
             .field("chunks", &self.chunks)
@@ -1,9 +1,15 @@
 mod component;
+mod dynamic;
 mod meta;
+mod relation;
 use std::any::{Any, TypeId};
 
 pub use component::{Component, Components};
+pub use dynamic::{ComponentDescriptor, ComponentId};
+pub(crate) use dynamic::{DynamicComponents, RawComponent};
 pub(crate) use meta::BundleMeta;
+pub use relation::{Related, Relation};
+pub(crate) use relation::Relations;
 pub use trecs_proc::{Bundle, Component};
 
 /// 一系列[Component]的组合
@@ -18,6 +24,15 @@ pub trait Bundle: Any {
     /// [Bundle]中所有[Component]的[TypeId]
     fn components_ids() -> &'static [TypeId];
 
+    /// [Bundle]中所有[Component]的类型名,和components_ids一一对应
+    ///
+    /// 默认全部是"?",手动实现[Bundle]时可以覆盖
+    fn components_names() -> &'static [&'static str] {
+        component::cached_names(Self::type_id_(), || {
+            vec!["?"; Self::components_ids().len()]
+        })
+    }
+
     /// 还原并[Drop]
     ///
     /// 主要是传递给[BundleMeta]，作为[World][Drop]时的调用
@@ -0,0 +1,354 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    fmt::Debug,
+    marker::PhantomData,
+    ops::Deref,
+};
+
+use crate::{bundle::Component, storage::Entity, world::World};
+
+/// 两个[Entity]之间的关系
+///
+/// 和[Component]一样,此特征实际上只是一个标记
+///
+/// 比如`struct Likes;`,那么(Likes,target)就是一对关系
+///
+/// [Component]:crate
+pub trait Relation: Any {}
+
+/// 记录source通过[Relation]R指向的所有target的[Component]
+///
+/// 由[Command::relate]和[Command::unrelate]维护,最后一个target被移除时也会被移除
+///
+/// 所以可以在[Query]中按照[Relation]筛选: `Query<&Related<Likes>>`,`With<Related<Likes>>`,
+/// 按照target筛选见[Query::iter_targeting]
+///
+/// [Command::relate]: crate::tools::Command::relate
+/// [Command::unrelate]: crate::tools::Command::unrelate
+/// [Query]: crate::world::Query
+/// [Query::iter_targeting]: crate::world::Query::iter_targeting
+pub struct Related<R: Relation> {
+    targets: Vec<Entity>,
+    _p: PhantomData<R>,
+}
+
+impl<R: Relation> Deref for Related<R> {
+    type Target = [Entity];
+
+    fn deref(&self) -> &Self::Target {
+        &self.targets
+    }
+}
+
+impl<R: Relation> Debug for Related<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Related").field(&self.targets).finish()
+    }
+}
+
+impl<R: Relation> Component for Related<R> {
+    fn type_id_() -> TypeId {
+        TypeId::of::<Self>()
+    }
+}
+
+/// 让source上的[Related]和[Relations]中的记录一致
+fn sync<R: Relation>(world: &mut World, source: Entity) {
+    let targets = world.relations.targets::<R>(source).to_vec();
+    if targets.is_empty() {
+        world.detach::<Related<R>>(source);
+    } else if let Some(related) = world.get_mut::<Related<R>>(source) {
+        related.targets = targets;
+    } else {
+        world.attach(
+            source,
+            Related::<R> {
+                targets,
+                _p: PhantomData,
+            },
+        );
+    }
+}
+
+/// 更新source上的[Related]的函数
+type SyncFn = fn(&mut World, Entity);
+
+/// 一种[Relation]的所有关系
+///
+/// 同时记录正向和反向的索引,两者始终保持一致
+pub struct RelationMeta {
+    /// source -> 所有target
+    targets: HashMap<Entity, Vec<Entity>>,
+    /// target -> 所有source
+    sources: HashMap<Entity, Vec<Entity>>,
+    /// 更新source上的[Related]
+    sync: SyncFn,
+}
+
+impl RelationMeta {
+    fn new<R: Relation>() -> Self {
+        Self {
+            targets: Default::default(),
+            sources: Default::default(),
+            sync: sync::<R>,
+        }
+    }
+
+    /// 添加一对关系
+    ///
+    /// 返回关系是否是新添加的
+    fn insert(&mut self, source: Entity, target: Entity) -> bool {
+        let targets = self.targets.entry(source).or_default();
+        if targets.contains(&target) {
+            return false;
+        }
+        targets.push(target);
+        self.sources.entry(target).or_default().push(source);
+        true
+    }
+
+    /// 删除一对关系
+    ///
+    /// 返回关系是否存在
+    fn remove(&mut self, source: Entity, target: Entity) -> bool {
+        let existed = remove_from(&mut self.targets, source, target);
+        remove_from(&mut self.sources, target, source);
+        existed
+    }
+
+    /// 删除所有和[Entity]有关的关系
+    ///
+    /// 返回失去了target的所有source
+    fn forget(&mut self, entity: Entity) -> Vec<Entity> {
+        for target in self.targets.remove(&entity).unwrap_or_default() {
+            remove_from(&mut self.sources, target, entity);
+        }
+        let sources = self.sources.remove(&entity).unwrap_or_default();
+        for source in &sources {
+            remove_from(&mut self.targets, *source, entity);
+        }
+        sources
+    }
+}
+
+/// 从key对应的列表中删除value,列表为空时删除整个列表
+///
+/// 返回value是否存在
+fn remove_from(map: &mut HashMap<Entity, Vec<Entity>>, key: Entity, value: Entity) -> bool {
+    let Some(values) = map.get_mut(&key) else {
+        return false;
+    };
+    let Some(idx) = values.iter().position(|v| *v == value) else {
+        return false;
+    };
+    values.swap_remove(idx);
+    if values.is_empty() {
+        map.remove(&key);
+    }
+    true
+}
+
+/// [World]中所有的[Relation]
+///
+/// 在[Entity]被删除时,所有和它有关的关系也会被删除
+///
+/// [World]:crate
+#[derive(Default)]
+pub struct Relations {
+    inner: HashMap<TypeId, (&'static str, RelationMeta)>,
+}
+
+impl Relations {
+    pub fn insert<R: Relation>(&mut self, source: Entity, target: Entity) -> bool {
+        self.inner
+            .entry(TypeId::of::<R>())
+            .or_insert_with(|| (type_name::<R>(), RelationMeta::new::<R>()))
+            .1
+            .insert(source, target)
+    }
+
+    /// 更新source上的[Related]
+    pub fn sync<R: Relation>(world: &mut World, source: Entity) {
+        sync::<R>(world, source)
+    }
+
+    pub fn remove<R: Relation>(&mut self, source: Entity, target: Entity) -> bool {
+        self.inner
+            .get_mut(&TypeId::of::<R>())
+            .map(|(.., meta)| meta.remove(source, target))
+            .unwrap_or(false)
+    }
+
+    pub fn targets<R: Relation>(&self, source: Entity) -> &[Entity] {
+        self.inner
+            .get(&TypeId::of::<R>())
+            .and_then(|(.., meta)| meta.targets.get(&source))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn sources<R: Relation>(&self, target: Entity) -> &[Entity] {
+        self.inner
+            .get(&TypeId::of::<R>())
+            .and_then(|(.., meta)| meta.sources.get(&target))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn pairs<R: Relation>(&self) -> Vec<(Entity, Entity)> {
+        self.inner
+            .get(&TypeId::of::<R>())
+            .map(|(.., meta)| {
+                meta.targets
+                    .iter()
+                    .flat_map(|(&source, targets)| {
+                        targets.iter().map(move |&target| (source, target))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 删除所有和[Entity]有关的关系
+    ///
+    /// 返回需要对哪些source调用哪个函数来更新[Related]
+    pub fn forget(&mut self, entity: Entity) -> Vec<(SyncFn, Entity)> {
+        self.inner
+            .values_mut()
+            .flat_map(|(.., meta)| {
+                let sync = meta.sync;
+                meta.forget(entity)
+                    .into_iter()
+                    .map(move |source| (sync, source))
+            })
+            .collect()
+    }
+}
+
+impl Debug for Relations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.inner
+                    .values()
+                    .map(|(name, meta)| (name, &meta.targets)),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tools::Command, World};
+
+    use super::*;
+
+    struct Likes;
+    impl Relation for Likes {}
+
+    struct Targets;
+    impl Relation for Targets {}
+
+    #[test]
+    fn relate() {
+        let mut world = World::new();
+        let boss = world.spawn("boss");
+        let a = world.spawn(1);
+        let b = world.spawn(2);
+
+        assert!(world.relate::<Targets>(a, boss));
+        assert!(world.relate::<Targets>(b, boss));
+        assert!(world.relate::<Likes>(a, b));
+        // 重复的关系只记录一次
+        assert!(world.relate::<Likes>(a, b));
+        assert_eq!(world.targets::<Likes>(a), &[b]);
+
+        let mut sources = world.sources::<Targets>(boss).to_vec();
+        sources.sort();
+        assert_eq!(sources, vec![a, b]);
+        assert!(world.sources::<Likes>(boss).is_empty());
+        assert_eq!(world.pairs::<Likes>(), vec![(a, b)]);
+
+        assert!(world.unrelate::<Targets>(a, boss));
+        assert!(!world.unrelate::<Targets>(a, boss));
+        assert_eq!(world.sources::<Targets>(boss), &[b]);
+
+        // 失效的Entity不能建立关系
+        world.remove(b);
+        assert!(!world.relate::<Likes>(a, b));
+    }
+
+    #[test]
+    fn cleanup() {
+        let mut world = World::new();
+        let boss = world.spawn("boss");
+        let a = world.spawn(1);
+        let b = world.spawn(2);
+
+        world.relate::<Targets>(a, boss);
+        world.relate::<Targets>(b, boss);
+        world.relate::<Likes>(boss, a);
+
+        // 删除target
+        world.remove(boss);
+        assert!(world.targets::<Targets>(a).is_empty());
+        assert!(world.targets::<Targets>(b).is_empty());
+        assert!(world.pairs::<Targets>().is_empty());
+
+        // 删除source
+        world.relate::<Likes>(a, b);
+        world.remove(a);
+        assert!(world.sources::<Likes>(b).is_empty());
+        assert!(world.pairs::<Likes>().is_empty());
+    }
+
+    #[test]
+    fn query() {
+        use crate::{tools::With, world::Query};
+
+        let mut world = World::new();
+        let boss = world.spawn("boss");
+        let a = world.spawn(1);
+        let b = world.spawn(2);
+        let c = world.spawn(3);
+
+        world.relate::<Targets>(a, boss);
+        world.relate::<Targets>(b, boss);
+        world.relate::<Likes>(c, a);
+
+        // 按照Relation筛选
+        let mut values = Query::<&i32, With<Related<Targets>>>::new(&mut world)
+            .into_iter()
+            .copied()
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![1, 2]);
+        assert_eq!(
+            Query::<&Related<Likes>>::new(&mut world)
+                .single()
+                .map(|r| r.to_vec()),
+            Ok(vec![a])
+        );
+
+        // 按照target筛选
+        let mut query = Query::<&mut i32>::new(&mut world);
+        for value in query.iter_targeting_mut::<Targets>(boss) {
+            *value *= 10;
+        }
+        let mut values = Query::<&i32>::new(&mut world)
+            .iter_targeting::<Targets>(boss)
+            .copied()
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![10, 20]);
+
+        // 最后一个target被删除时Related也被删除
+        world.unrelate::<Targets>(a, boss);
+        world.remove(boss);
+        world.remove(a);
+        assert!(Query::<&Related<Targets>>::new(&mut world).is_empty());
+        assert!(Query::<&Related<Likes>>::new(&mut world).is_empty());
+        assert_eq!(world.fetch::<&i32>(c).copied(), Some(3));
+    }
+}
@@ -0,0 +1,167 @@
+use std::{error::Error, fmt::Display};
+
+/// trecs中可以恢复的错误
+///
+/// Display输出英文,方便写入日志
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TrecsError {
+    /// 同一个[System]中的两个[WorldFetch]使用了同一种[Component],并且至少一个是可变引用
+    ///
+    /// [System]: crate::system::System
+    /// [WorldFetch]: crate::tools::WorldFetch
+    /// [Component]: crate
+    AliasConflict {
+        /// 发生冲突的[Component]的类型名
+        ///
+        /// [Component]: crate
+        component: &'static str,
+        /// 先出现的[WorldFetch]的类型名
+        ///
+        /// [WorldFetch]: crate::tools::WorldFetch
+        first: &'static str,
+        first_mut: bool,
+        /// 后出现的[WorldFetch]的类型名
+        ///
+        /// [WorldFetch]: crate::tools::WorldFetch
+        second: &'static str,
+        second_mut: bool,
+    },
+    /// 同一个[System]中出现了两个相同的[Res]
+    ///
+    /// [System]: crate::system::System
+    /// [Res]: crate::world::Res
+    DuplicateRes {
+        /// 资源的类型名
+        resource: &'static str,
+    },
+    /// [Resources]和其他[Resources]或者任何[Res]出现在同一个[System]中
+    ///
+    /// [Resources]: crate::world::Resources
+    /// [Res]: crate::world::Res
+    ResourcesConflict {
+        /// 冲突的参数的类型名
+        param: &'static str,
+    },
+    /// 重复添加了只能添加一次的[Plugin]
+    ///
+    /// [Plugin]: crate::app::Plugin
+    DuplicatePlugin {
+        /// [Plugin::name]
+        ///
+        /// [Plugin::name]: crate::app::Plugin::name
+        plugin: &'static str,
+    },
+    /// 用同一个名字注册了布局或者Drop函数不同的运行时[Component]
+    ///
+    /// [Component]: crate
+    ComponentMismatch {
+        /// [Component]的名字
+        ///
+        /// [Component]: crate
+        component: &'static str,
+    },
+}
+
+impl Display for TrecsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let access = |mutable: bool| if mutable { "mutable" } else { "immutable" };
+        match self {
+            Self::AliasConflict {
+                component,
+                first,
+                first_mut,
+                second,
+                second_mut,
+            } => write!(
+                f,
+                "alias conflict on component `{component}`: \
+                 fetch `{first}` takes a {} reference while fetch `{second}` takes a {} reference",
+                access(*first_mut),
+                access(*second_mut),
+            ),
+            Self::DuplicateRes { resource } => {
+                write!(
+                    f,
+                    "resource `{resource}` is used more than once in one system"
+                )
+            }
+            Self::ResourcesConflict { param } => write!(
+                f,
+                "`Resources` cannot be used together with `{param}` in one system"
+            ),
+            Self::DuplicatePlugin { plugin } => {
+                write!(f, "plugin `{plugin}` has already been added")
+            }
+            Self::ComponentMismatch { component } => write!(
+                f,
+                "component `{component}` is already registered with a different layout or drop function"
+            ),
+        }
+    }
+}
+
+impl Error for TrecsError {}
+
+/// [System]返回的错误
+///
+/// 交给[World::set_error_handler]设置的函数处理,默认会panic
+///
+/// [System]: crate::system::System
+/// [World::set_error_handler]: crate::World::set_error_handler
+#[cfg(feature = "system")]
+#[derive(Debug)]
+pub struct SystemError {
+    /// 返回错误的[System]的类型名
+    ///
+    /// [System]: crate::system::System
+    pub system: &'static str,
+    pub error: Box<dyn Error>,
+}
+
+#[cfg(feature = "system")]
+impl SystemError {
+    pub(crate) fn new(system: &'static str, error: Box<dyn Error>) -> Self {
+        Self { system, error }
+    }
+}
+
+#[cfg(feature = "system")]
+impl Display for SystemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "system `{}` failed: {}", self.system, self.error)
+    }
+}
+
+#[cfg(feature = "system")]
+impl Error for SystemError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// 可以传给[World::set_error_handler]的错误处理函数
+///
+/// [World::set_error_handler]: crate::World::set_error_handler
+#[cfg(feature = "system")]
+pub mod handler {
+    use super::SystemError;
+    use crate::{tools::ResManager, World};
+
+    /// 直接panic,这是默认的处理方式
+    pub fn panic(_world: &mut World, error: SystemError) {
+        panic!("{error}")
+    }
+
+    /// 输出到标准错误
+    pub fn log(_world: &mut World, error: SystemError) {
+        eprintln!("{error}")
+    }
+
+    /// 收集到`Vec<SystemError>`资源中
+    pub fn collect(world: &mut World, error: SystemError) {
+        let mut errors = world.get_res::<Vec<SystemError>>();
+        errors.get_or_init(Vec::new);
+        errors.get_mut().unwrap().push(error);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{any::TypeId, ops::Deref};
+
+#[allow(unused_imports)]
+use crate::tools::Command;
+use crate::{bundle::Component, storage::Entity, world::World};
+
+/// 指向父节点的[Component]
+///
+/// 由[Command::set_parent]和[Command::remove_parent]维护,
+/// 和父节点上的[Children]保持一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Parent(pub(crate) Entity);
+
+impl Parent {
+    /// 父节点的[Entity]
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+impl Component for Parent {
+    fn type_id_() -> TypeId {
+        TypeId::of::<Self>()
+    }
+}
+
+/// 记录所有子节点的[Component]
+///
+/// 由[Command::set_parent]和[Command::remove_parent]维护,
+/// 和子节点上的[Parent]保持一致
+///
+/// 最后一个子节点被移除时,[Children]也会被移除
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Children(pub(crate) Vec<Entity>);
+
+impl Deref for Children {
+    type Target = [Entity];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Component for Children {
+    fn type_id_() -> TypeId {
+        TypeId::of::<Self>()
+    }
+}
+
+/// 沿着[Parent]向上遍历所有祖先的迭代器
+///
+/// 由近及远,不包括起点本身
+pub struct Ancestors<'a> {
+    world: &'a World,
+    current: Option<Entity>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let parent = self.world.get::<Parent>(self.current?).map(Parent::get);
+        self.current = parent;
+        parent
+    }
+}
+
+/// 沿着[Children]向下遍历所有后代的迭代器
+///
+/// 深度优先,不包括起点本身
+pub struct Descendants<'a> {
+    world: &'a World,
+    stack: Vec<Entity>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entity = self.stack.pop()?;
+        if let Some(children) = self.world.get::<Children>(entity) {
+            // 倒序入栈,保证先访问第一个子节点
+            self.stack.extend(children.iter().rev());
+        }
+        Some(entity)
+    }
+}
+
+impl World {
+    /// 遍历[Entity]的所有祖先
+    pub fn ancestors(&self, entity: Entity) -> Ancestors<'_> {
+        Ancestors {
+            world: self,
+            current: Some(entity),
+        }
+    }
+
+    /// 遍历[Entity]的所有后代
+    pub fn descendants(&self, entity: Entity) -> Descendants<'_> {
+        let stack = self
+            .get::<Children>(entity)
+            .map(|children| children.iter().rev().copied().collect())
+            .unwrap_or_default();
+        Descendants { world: self, stack }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{iter::EIter, world::Query};
+
+    #[test]
+    fn set_parent() {
+        let mut world = World::new();
+        let tank = world.spawn(1usize);
+        let turret = world.spawn((2usize, "turret"));
+        let gun = world.spawn(3usize);
+
+        assert!(world.set_parent(turret, tank));
+        assert!(world.set_parent(gun, turret));
+
+        // 不能形成环
+        assert!(!world.set_parent(tank, gun));
+        assert!(!world.set_parent(tank, tank));
+
+        // 添加Component之后,原来的Entity仍然有效
+        assert_eq!(world.fetch::<&usize>(turret).copied(), Some(2));
+        assert_eq!(world.fetch::<&&str>(turret).copied(), Some("turret"));
+        assert_eq!(world.get::<Parent>(turret).map(Parent::get), Some(tank));
+        assert_eq!(
+            world.get::<Children>(tank).map(|c| c.to_vec()),
+            Some(vec![turret])
+        );
+
+        assert_eq!(world.ancestors(gun).collect::<Vec<_>>(), vec![turret, tank]);
+        assert_eq!(
+            world.descendants(tank).collect::<Vec<_>>(),
+            vec![turret, gun]
+        );
+
+        // 被移动过的Entity仍然可以被Query到
+        let mut values = Query::<&usize>::new(&mut world)
+            .into_iter()
+            .copied()
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![1, 2, 3]);
+
+        // 重新设置父节点
+        assert!(world.set_parent(gun, tank));
+        assert!(world.get::<Children>(turret).is_none());
+        assert_eq!(
+            world.get::<Children>(tank).map(|c| c.to_vec()),
+            Some(vec![turret, gun])
+        );
+
+        assert_eq!(world.remove_parent(gun), Some(tank));
+        assert_eq!(world.remove_parent(gun), None);
+        assert_eq!(
+            world.get::<Children>(tank).map(|c| c.to_vec()),
+            Some(vec![turret])
+        );
+    }
+
+    #[test]
+    fn despawn() {
+        let mut world = World::new();
+        let root = world.spawn(0usize);
+        let a = world.spawn(1usize);
+        let b = world.spawn(2usize);
+        let c = world.spawn(3usize);
+        let d = world.spawn(4usize);
+
+        world.set_parent(a, root);
+        world.set_parent(b, a);
+        world.set_parent(c, a);
+        world.set_parent(d, root);
+
+        // 删除中间的节点,子节点变为根节点
+        assert!(world.remove(d));
+        assert_eq!(
+            world.get::<Children>(root).map(|c| c.to_vec()),
+            Some(vec![a])
+        );
+
+        assert!(world.despawn_recursive(a));
+        for entity in [a, b, c] {
+            assert_eq!(world.alive(entity), Some(false));
+        }
+        assert!(world.get::<Children>(root).is_none());
+        assert_eq!(world.fetch::<&usize>(root).copied(), Some(0));
+
+        // 删除父节点后,子节点失去Parent
+        let e = world.spawn(5usize);
+        world.set_parent(e, root);
+        world.remove(root);
+        assert!(world.get::<Parent>(e).is_none());
+        assert_eq!(world.fetch::<&usize>(e).copied(), Some(5));
+    }
+
+    #[test]
+    fn query_ancestors() {
+        use crate::error::TrecsError;
+
+        fn depth(
+            parents: Query<&Parent>,
+            children: Query<&Children>,
+            mut depths: Query<&mut usize>,
+        ) {
+            let mut descendants = 0;
+            for mut item in EIter::from(depths.iter_mut()) {
+                let entity = item.entity();
+                **item = parents.ancestors(entity).count();
+                descendants += children.descendants(entity).count();
+            }
+            assert_eq!(descendants, 3);
+        }
+        // 读取Parent的同时修改Parent
+        fn conflict(_parents: Query<&Parent>, _other: Query<&mut Parent>) {}
+
+        let mut world = World::new();
+        let root = world.spawn(0usize);
+        let a = world.spawn(0usize);
+        let b = world.spawn(0usize);
+        world.set_parent(a, root);
+        world.set_parent(b, a);
+        world.exec(depth);
+        assert_eq!(world.fetch::<&usize>(b).copied(), Some(2));
+
+        assert!(matches!(
+            world.try_add_system(conflict),
+            Err(TrecsError::AliasConflict { .. })
+        ));
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    any::{Any, TypeId},
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Debug,
+    hash::Hash,
+};
+
+use crate::{
+    bundle::Component,
+    iter::{EIter, Iter},
+    storage::Entity,
+    world::Query,
+    World,
+};
+
+/// 按照[Component]的值查找[Entity]的索引
+///
+/// 通过[World::add_index]添加
+///
+/// + 新增,删除[Entity],以及增删C时立即更新,
+///   包括[Command::spawn],[Command::remove],[EntityMut::insert]和加载场景等
+/// + 通过get_mut或者可写的[Query]修改的值在[World::maintain]时更新,
+///   在这之前[Index::lookup]返回的是旧的值
+///
+/// 索引需要保存值的副本,C被修改或者删除之后才能找到旧的键,所以要求C: [Clone]
+///
+/// [Command::spawn]:crate::tools::Command::spawn
+/// [Command::remove]:crate::tools::Command::remove
+/// [EntityMut::insert]:crate::world::EntityMut::insert
+pub struct Index<C: Component + Hash + Eq + Clone> {
+    entities: HashMap<C, Vec<Entity>>,
+    values: HashMap<Entity, C>,
+}
+
+impl<C: Component + Hash + Eq + Clone> Index<C> {
+    fn new() -> Self {
+        Self {
+            entities: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    /// 所有值为value的[Entity]
+    pub fn lookup(&self, value: &C) -> &[Entity] {
+        self.entities.get(value).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// 索引中[Entity]的值
+    pub fn get(&self, entity: Entity) -> Option<&C> {
+        self.values.get(&entity)
+    }
+
+    /// 索引中[Entity]的数量
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn insert(&mut self, entity: Entity, value: C) {
+        if self.values.get(&entity) == Some(&value) {
+            return;
+        }
+        self.forget(entity);
+        self.entities.entry(value.clone()).or_default().push(entity);
+        self.values.insert(entity, value);
+    }
+
+    fn forget(&mut self, entity: Entity) {
+        let Some(value) = self.values.remove(&entity) else {
+            return;
+        };
+        if let Some(entities) = self.entities.get_mut(&value) {
+            entities.retain(|e| *e != entity);
+            if entities.is_empty() {
+                self.entities.remove(&value);
+            }
+        }
+    }
+
+    /// 重新读取[Entity]上C的值,不存在时从索引中移除
+    fn refresh(&mut self, world: &World, entity: Entity) {
+        match world.get::<C>(entity) {
+            Some(value) => self.insert(entity, value.clone()),
+            None => self.forget(entity),
+        }
+    }
+
+    /// 为[World]中所有的C建立索引
+    fn rebuild(&mut self, world: &mut World) {
+        for e in EIter::from(Query::<&C>::new(world).into_iter()) {
+            self.insert(e.entity(), (*e).clone());
+        }
+    }
+
+    /// 和[World]中的[Component]同步
+    ///
+    /// 只会检查上一次同步之后可能被修改过的[Entity]
+    fn update(&mut self, world: &mut World) {
+        let changed = world.changes.take(TypeId::of::<Self>());
+        for meta in changed.archetypes {
+            let Some(mapping_table) = world.metas[meta].fetch::<&C>() else {
+                continue;
+            };
+            let iter = Iter::<&C>::from_matched(world, Cow::Owned(vec![(meta, mapping_table)]));
+            for e in EIter::from(iter) {
+                self.insert(e.entity(), (*e).clone());
+            }
+        }
+        for entity in changed.entities {
+            self.refresh(world, entity);
+        }
+    }
+}
+
+impl<C: Component + Hash + Eq + Clone + Debug> Debug for Index<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(&self.entities).finish()
+    }
+}
+
+/// 擦除了类型的[Index]
+trait AnyIndex {
+    /// 索引的[Component]的[TypeId]
+    fn component(&self) -> TypeId;
+
+    /// 重新读取[Entity]上的值
+    fn refresh(&mut self, world: &World, entity: Entity);
+
+    fn forget(&mut self, entity: Entity);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<C: Component + Hash + Eq + Clone> AnyIndex for Index<C> {
+    fn component(&self) -> TypeId {
+        C::type_id_()
+    }
+
+    fn refresh(&mut self, world: &World, entity: Entity) {
+        Index::refresh(self, world, entity)
+    }
+
+    fn forget(&mut self, entity: Entity) {
+        Index::forget(self, entity)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// [World]中所有的[Index]
+#[derive(Default)]
+pub(crate) struct Indices {
+    inner: HashMap<TypeId, Box<dyn AnyIndex>>,
+}
+
+impl Indices {
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// 重新读取entities上的值
+    ///
+    /// components为[Some]时只更新这些[Component]的索引
+    pub fn refresh(&mut self, world: &World, entities: &[Entity], components: Option<&[TypeId]>) {
+        for index in self.inner.values_mut() {
+            if components.is_some_and(|ids| !ids.contains(&index.component())) {
+                continue;
+            }
+            for &entity in entities {
+                index.refresh(world, entity);
+            }
+        }
+    }
+
+    pub fn forget(&mut self, entity: Entity) {
+        for index in self.inner.values_mut() {
+            index.forget(entity);
+        }
+    }
+}
+
+impl Debug for Indices {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.inner.keys()).finish()
+    }
+}
+
+impl World {
+    /// 添加以C的值为键的[Index]
+    ///
+    /// 会立即为已经存在的[Entity]建立索引,已经存在时什么都不做
+    pub fn add_index<C: Component + Hash + Eq + Clone>(&mut self) -> &mut Self {
+        let id = TypeId::of::<Index<C>>();
+        if !self.indices.inner.contains_key(&id) {
+            self.changes.track(id, C::type_id_());
+            let mut index = Index::<C>::new();
+            index.rebuild(self);
+            self.indices.inner.insert(id, Box::new(index));
+            self.maintainers.push(World::update_index::<C>);
+        }
+        self
+    }
+
+    /// 以C的值为键的[Index]
+    pub fn index<C: Component + Hash + Eq + Clone>(&self) -> Option<&Index<C>> {
+        self.indices
+            .inner
+            .get(&TypeId::of::<Index<C>>())?
+            .as_any()
+            .downcast_ref()
+    }
+
+    /// 立即和[World]中C的值同步,即通过get_mut等修改过的值
+    ///
+    /// 没有添加索引时什么都不做
+    pub fn update_index<C: Component + Hash + Eq + Clone>(&mut self) {
+        let id = TypeId::of::<Index<C>>();
+        let Some(mut index) = self.indices.inner.remove(&id) else {
+            return;
+        };
+        if let Some(index) = index.as_any_mut().downcast_mut::<Index<C>>() {
+            index.update(self);
+        }
+        self.indices.inner.insert(id, index);
+    }
+
+    /// 为新出现的[Entity]建立索引
+    pub(crate) fn index_spawned(&mut self, entities: &[Entity]) {
+        self.index_refresh(entities, None);
+    }
+
+    /// 增删[Component]之后立即更新对应的索引
+    pub(crate) fn index_changed(&mut self, entity: Entity, components: &[TypeId]) {
+        self.index_refresh(&[entity], Some(components));
+    }
+
+    fn index_refresh(&mut self, entities: &[Entity], components: Option<&[TypeId]>) {
+        if self.indices.is_empty() {
+            return;
+        }
+        let mut indices = std::mem::take(&mut self.indices);
+        indices.refresh(self, entities, components);
+        self.indices = indices;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use crate::{bundle::Component, tools::Command, world::Query, World};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Name(&'static str);
+
+    impl Component for Name {
+        fn type_id_() -> TypeId {
+            TypeId::of::<Self>()
+        }
+    }
+
+    #[test]
+    fn index() {
+        let mut world = World::new();
+        let alice = world.spawn(Name("alice"));
+        world.add_index::<Name>();
+        assert_eq!(
+            world.index::<Name>().unwrap().lookup(&Name("alice")),
+            &[alice]
+        );
+
+        let bob = world.spawn((Name("bob"), 1u8));
+        let many = world.spawn_many([(Name("bob"), 2u8), (Name("carol"), 3u8)]);
+        let index = world.index::<Name>().unwrap();
+        assert_eq!(index.lookup(&Name("bob")), &[bob, many[0]]);
+        assert_eq!(index.lookup(&Name("carol")), &[many[1]]);
+        assert_eq!(index.len(), 4);
+
+        world.remove(bob);
+        assert_eq!(
+            world.index::<Name>().unwrap().lookup(&Name("bob")),
+            &[many[0]]
+        );
+
+        // 增删Component时立即更新
+        world.detach::<Name>(many[1]);
+        assert!(world
+            .index::<Name>()
+            .unwrap()
+            .lookup(&Name("carol"))
+            .is_empty());
+        world.entity_mut(many[1]).unwrap().insert(Name("frank"));
+        assert_eq!(
+            world.index::<Name>().unwrap().lookup(&Name("frank")),
+            &[many[1]]
+        );
+        world.detach::<Name>(many[1]);
+
+        // 值的变化在maintain时更新
+        *world.get_mut::<Name>(alice).unwrap() = Name("dave");
+        assert_eq!(
+            world.index::<Name>().unwrap().lookup(&Name("alice")),
+            &[alice]
+        );
+        world.maintain();
+        let index = world.index::<Name>().unwrap();
+        assert!(index.lookup(&Name("alice")).is_empty());
+        assert!(index.lookup(&Name("carol")).is_empty());
+        assert_eq!(index.lookup(&Name("dave")), &[alice]);
+        assert_eq!(index.get(alice), Some(&Name("dave")));
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn query_and_scene() {
+        let mut world = World::new();
+        world.type_registry().register::<i32>().scene();
+        world.add_index::<i32>();
+        let e = world.spawn(1);
+
+        for value in Query::<&mut i32>::new(&mut world).iter_mut() {
+            *value = 2;
+        }
+        assert_eq!(world.index::<i32>().unwrap().lookup(&1), &[e]);
+        world.maintain();
+        assert_eq!(world.index::<i32>().unwrap().lookup(&2), &[e]);
+
+        let loaded = world
+            .load_scene("# trecs scene\nentity 0\n    i32 = 7\n".as_bytes())
+            .unwrap();
+        assert_eq!(world.index::<i32>().unwrap().lookup(&7), &loaded[..]);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{borrow::Cow, marker::PhantomData};
+
+use crate::{
+    storage::Chunk,
+    tools::{MappingTable, ReadOnlyFetch, WorldFetch},
+};
+
+use super::Iter;
+
+/// 遍历[Query]结果中所有K个不同结果的组合
+///
+/// 每个组合中的结果按照遍历的顺序排列,同一组结果只会出现一次
+///
+/// + 只读的[Query]可以直接作为[Iterator]使用
+/// + 可变的[Query]只能通过fetch_next逐个访问,上一个组合的借用结束后才能得到下一个组合
+///
+/// [Query]: crate::world::Query
+pub struct Combinations<'a, F: WorldFetch, const K: usize> {
+    matched: Cow<'a, [(usize, MappingTable)]>,
+    /// 所有可以访问的位置
+    slots: Vec<(usize, &'a Chunk, usize)>,
+    /// 下一个组合在slots中的下标,严格递增
+    indices: [usize; K],
+    done: bool,
+    _f: PhantomData<&'a F>,
+}
+
+impl<'a, F: WorldFetch, const K: usize> Combinations<'a, F, K> {
+    pub(crate) fn new(mut iter: Iter<'a, F>) -> Self {
+        let slots = std::iter::from_fn(|| iter.next_slot()).collect::<Vec<_>>();
+        Self {
+            done: K == 0 || K > slots.len(),
+            matched: iter.matched,
+            slots,
+            indices: std::array::from_fn(|i| i),
+            _f: PhantomData,
+        }
+    }
+
+    /// 下一个组合
+    ///
+    /// 返回的结果借用了[Combinations],所以可变的结果不会同时存在
+    pub fn fetch_next(&mut self) -> Option<[F::Item<'_>; K]> {
+        let indices = self.advance()?;
+        Some(std::array::from_fn(|i| {
+            let (meta, chunk, index) = self.slots[indices[i]];
+            // 同一个组合中的位置互不相同,所以不会产生别名
+            unsafe { F::build(chunk.get(index), &self.matched[meta].1) }
+        }))
+    }
+
+    /// 返回当前的组合,并移动到下一个组合
+    fn advance(&mut self) -> Option<[usize; K]> {
+        if self.done {
+            return None;
+        }
+        let current = self.indices;
+        let n = self.slots.len();
+        // 找到最右边还能增加的下标,并重置它之后的所有下标
+        match (0..K).rev().find(|&i| self.indices[i] < n - K + i) {
+            Some(i) => {
+                self.indices[i] += 1;
+                for j in i + 1..K {
+                    self.indices[j] = self.indices[j - 1] + 1;
+                }
+            }
+            None => self.done = true,
+        }
+        Some(current)
+    }
+}
+
+impl<'a, F: ReadOnlyFetch, const K: usize> Iterator for Combinations<'a, F, K> {
+    type Item = [F::Item<'a>; K];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let indices = self.advance()?;
+        Some(std::array::from_fn(|i| {
+            let (meta, chunk, index) = self.slots[indices[i]];
+            unsafe { F::build(chunk.get(index), &self.matched[meta].1) }
+        }))
+    }
+}
@@ -63,8 +63,21 @@ impl<'a, F: WorldFetch> Iterator for EIter<'a, F> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let item = self.inner.next()?;
-        let iter = &self.inner.iter.as_ref()?.1;
-        let entity = iter.chunk.gen_entity(iter.index);
-        Some(EBundle::new(entity, item))
+        let (chunk, index) = self.inner.last?;
+        Some(EBundle::new(chunk.gen_entity(index), item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, F: WorldFetch> DoubleEndedIterator for EIter<'a, F> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next_back()?;
+        let (chunk, index) = self.inner.last?;
+        Some(EBundle::new(chunk.gen_entity(index), item))
     }
 }
+
+impl<F: WorldFetch> ExactSizeIterator for EIter<'_, F> {}
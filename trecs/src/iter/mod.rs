@@ -1,102 +1,197 @@
+mod combinations;
 mod ebundle;
 
+pub use combinations::Combinations;
 pub use ebundle::{EBundle, EIter};
-use std::marker::PhantomData;
+use std::{borrow::Cow, marker::PhantomData};
 
 use crate::{
-    storage::{Chunk, ChunkIter},
-    tools::{
-        MappingTable, WorldFetch,
-        WorldFilter,
-    },
-    world::World,
+    storage::Chunk,
+    tools::{MappingTable, WorldFetch, WorldFilter},
+    world::{QueryState, World},
 };
 
-#[derive(Debug, Clone)]
-pub(crate) struct Select<'a> {
-    inner: Vec<(&'a MappingTable, Vec<&'a Chunk>)>,
-}
-
-impl Select<'_> {
-    pub fn new<F: WorldFetch, Q: WorldFilter>(world: &'_ mut World) -> Select<'_> {
-        world
-            .metas
-            .iter_mut()
-            .filter_map(|(.., meta)| {
-                if meta.filter::<Q>() && meta.fetch::<F>().is_some() {
-                    Some(meta)
-                } else {
-                    None
-                }
-            })
-            .filter_map(|meta| {
-                let chunks = meta
-                    .chunks
-                    .iter()
-                    .copied()
-                    .map(|cid| &world.chunks[cid])
-                    .collect::<Vec<_>>();
-                let mapping_table = meta.fetch::<F>()?;
-                Some((chunks, mapping_table))
-            })
-            .map(|(a, b)| (b, a))
-            .collect::<Vec<_>>()
-            .into()
-    }
-
-    pub fn pop(&mut self) -> Option<(&'_ MappingTable, &'_ Chunk)> {
-        let (mapping, chunks) = self.inner.last_mut()?;
-        let Some(chunk) = chunks.pop() else{
-            self.inner.pop();
-            return self.pop();
-        };
-        Some((mapping, chunk))
-    }
-}
+/// 匹配到的[BundleMeta]的下标,以及对应的[MappingTable]
+///
+/// [BundleMeta]: crate
+pub(crate) type Matched = Vec<(usize, MappingTable)>;
 
-impl<'a> From<Vec<(&'a MappingTable, Vec<&'a Chunk>)>> for Select<'a> {
-    fn from(value: Vec<(&'a MappingTable, Vec<&'a Chunk>)>) -> Self {
-        Self { inner: value }
-    }
+/// [Iter]遍历到的位置
+///
+/// meta是[BundleMeta]在matched中的下标,chunk是[Chunk]在[BundleMeta]中的下标
+///
+/// [BundleMeta]: crate
+#[derive(Debug, Clone, Copy, Default)]
+struct Cursor {
+    meta: usize,
+    chunk: usize,
+    index: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Iter<'a, F: WorldFetch> {
-    select: Select<'a>,
-    pub(crate) iter: Option<(&'a MappingTable, ChunkIter<'a>)>,
+    world: &'a World,
+    matched: Cow<'a, [(usize, MappingTable)]>,
+    /// 从前往后遍历的下一个位置
+    front: Cursor,
+    /// 从后往前遍历时,index是上一个位置
+    back: Cursor,
+    /// 还没有遍历的[Components]的数量
+    ///
+    /// 两端相遇时恰好为0
+    ///
+    /// [Components]: crate
+    remaining: usize,
+    /// 最后一次返回的[Components]所在的位置,用来生成[Entity]
+    ///
+    /// [Components]: crate
+    /// [Entity]: crate
+    pub(crate) last: Option<(&'a Chunk, usize)>,
     _f: PhantomData<&'a F>,
 }
 
 impl<'a, F: WorldFetch> Iter<'a, F> {
     pub fn new<Q: WorldFilter>(world: &mut World) -> Iter<'_, F> {
-        let select = Select::new::<F, Q>(world);
+        let mut state = QueryState::<F, Q>::new();
+        state.update(world);
+        Iter::from_matched(world, Cow::Owned(state.matched))
+    }
 
+    pub(crate) fn from_matched(
+        world: &'a World,
+        matched: Cow<'a, [(usize, MappingTable)]>,
+    ) -> Self {
+        let remaining = count(world, &matched);
+        let back = Cursor {
+            meta: matched.len(),
+            ..Default::default()
+        };
         Iter {
-            select,
-            iter: None,
+            world,
+            matched,
+            front: Default::default(),
+            back,
+            remaining,
+            last: None,
             _f: PhantomData,
         }
     }
+
+    /// matched中第meta个[BundleMeta]的所有[Chunk]
+    ///
+    /// [BundleMeta]: crate
+    fn chunks(&self, meta: usize) -> &'a [usize] {
+        &self.world.metas[self.matched[meta].0].chunks
+    }
+
+    fn build(&mut self, meta: usize, chunk: &'a Chunk, index: usize) -> F::Item<'a> {
+        self.last = Some((chunk, index));
+        let components = unsafe { chunk.get(index) };
+        unsafe { F::build(components, &self.matched[meta].1) }
+    }
+
+    /// 从前往后找到下一个可以访问的位置,但不生成结果
+    ///
+    /// 返回[BundleMeta]在matched中的下标,[Chunk]和[Components]在[Chunk]中的下标
+    ///
+    /// [BundleMeta]: crate
+    /// [Components]: crate
+    fn next_slot(&mut self) -> Option<(usize, &'a Chunk, usize)> {
+        while self.remaining != 0 {
+            let Cursor { meta, chunk, index } = self.front;
+            let Some(&cid) = self.chunks(meta).get(chunk) else {
+                self.front = Cursor {
+                    meta: meta + 1,
+                    ..Default::default()
+                };
+                continue;
+            };
+            let chunk_ref = &self.world.chunks[cid];
+            if index >= chunk_ref.len() {
+                self.front = Cursor {
+                    meta,
+                    chunk: chunk + 1,
+                    index: 0,
+                };
+                continue;
+            }
+
+            self.front.index += 1;
+            if chunk_ref.occupied(index) {
+                self.remaining -= 1;
+                return Some((meta, chunk_ref, index));
+            }
+        }
+        None
+    }
+}
+
+/// 所有匹配的[BundleMeta]中可以访问的[Components]的数量
+///
+/// [BundleMeta]: crate
+/// [Components]: crate
+pub(crate) fn count(world: &World, matched: &[(usize, MappingTable)]) -> usize {
+    matched
+        .iter()
+        .flat_map(|(meta, _)| &world.metas[*meta].chunks)
+        .map(|&cid| world.chunks[cid].count())
+        .sum()
+}
+
+impl<F: WorldFetch> std::fmt::Debug for Iter<'_, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Iter")
+            .field("matched", &self.matched)
+            .field("front", &self.front)
+            .field("back", &self.back)
+            .field("remaining", &self.remaining)
+            .finish()
+    }
 }
 
 impl<'a, F: WorldFetch> Iterator for Iter<'a, F> {
     type Item = F::Item<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.iter.is_none() {
-            // 这里的transmute是为了无界生命周期
-            let (mapping, chunk ) = unsafe { std::mem::transmute::<_,(&MappingTable,&Chunk)>(self.select.pop()?) };
-            let iter = chunk.iter();
-            self.iter = Some((mapping, iter));
-        }
-        let (mapping_table, iter) = self.iter.as_mut()?;
-        let Some(components) = iter.next()else{
-            
-            self.iter  = None;
-            return self.next();
-        };
+        let (meta, chunk, index) = self.next_slot()?;
+        Some(self.build(meta, chunk, index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, F: WorldFetch> DoubleEndedIterator for Iter<'a, F> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while self.remaining != 0 {
+            let Cursor { meta, chunk, index } = self.back;
+            // index为0时说明当前的Chunk已经遍历完了,移动到前一个Chunk
+            if index == 0 {
+                self.back = match chunk {
+                    0 => Cursor {
+                        meta: meta - 1,
+                        chunk: self.chunks(meta - 1).len(),
+                        index: 0,
+                    },
+                    _ => Cursor {
+                        meta,
+                        chunk: chunk - 1,
+                        index: self.world.chunks[self.chunks(meta)[chunk - 1]].len(),
+                    },
+                };
+                continue;
+            }
 
-        let item = unsafe { F::build(components, mapping_table) };
-        Some(item)
+            self.back.index -= 1;
+            let chunk_ref = &self.world.chunks[self.chunks(meta)[chunk]];
+            if chunk_ref.occupied(index - 1) {
+                self.remaining -= 1;
+                return Some(self.build(meta, chunk_ref, index - 1));
+            }
+        }
+        None
     }
 }
+
+impl<F: WorldFetch> ExactSizeIterator for Iter<'_, F> {}
@@ -1,16 +1,76 @@
+/// 通过[Plugin]组织[System]和资源的[App]
+///
+/// [Plugin]:crate
+/// [System]:crate
+/// [App]:crate
+#[cfg(feature = "system")]
+pub mod app;
 /// 定义[Bundle]相关
 ///
 /// 如[BundleMeta]等
 pub mod bundle;
+/// 错误类型
+///
+/// + [TrecsError]
+///
+/// [TrecsError]:crate
+pub mod error;
+/// [Entity]之间的层级关系
+///
+/// + [Parent]和[Children]
+/// + 遍历祖先和后代的迭代器
+///
+/// [Entity]:crate
+/// [Parent]:crate
+/// [Children]:crate
+pub mod hierarchy;
+/// 按照[Component]的值查找[Entity]的索引
+///
+/// + [Index]
+///
+/// [Component]:crate
+/// [Entity]:crate
+/// [Index]:crate
+pub mod index;
 /// [Components]迭代器
 ///
 /// [Components]: crate
 pub mod iter;
+/// 运行时的类型信息
+///
+/// + [TypeRegistry]
+///
+/// [TypeRegistry]:crate
+pub mod reflect;
+/// 保存和加载场景
+///
+/// + [SceneComponent]
+/// + [World::save_scene]和[World::load_scene]
+///
+/// [SceneComponent]:crate
+/// [World::save_scene]:crate
+/// [World::load_scene]:crate
+pub mod scene;
+/// 按照位置查找[Entity]的空间索引
+///
+/// + [Position]和[SpatialIndex]
+///
+/// [Entity]:crate
+/// [Position]:crate
+/// [SpatialIndex]:crate
+pub mod spatial;
 /// 存储[World]中数据的容器
 pub mod storage;
 
 #[cfg(feature = "system")]
 pub mod system;
+/// 在后台线程中执行的任务
+///
+/// + [TaskPool]和[Task]
+///
+/// [TaskPool]:crate
+/// [Task]:crate
+pub mod task;
 ///一些用于操作的trait,以及封装其中的[Command]
 ///
 /// + 对[World]进行[Entity]级操作的[Command]
@@ -19,5 +79,6 @@ pub mod system;
 /// [World]:crate
 pub mod tools;
 pub mod world;
+pub use error::TrecsError;
 /// 最终的容器
 pub use world::World;
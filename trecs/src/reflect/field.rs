@@ -0,0 +1,76 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    fmt::Debug,
+};
+
+type GetFn = Box<dyn for<'a> Fn(&'a dyn Any) -> Option<&'a dyn Any>>;
+type GetMutFn = Box<dyn for<'a> Fn(&'a mut dyn Any) -> Option<&'a mut dyn Any>>;
+
+/// 结构体中一个字段的信息
+///
+/// 由[TypeBuilder::field]创建
+///
+/// [TypeBuilder::field]:crate
+pub struct FieldInfo {
+    name: &'static str,
+    type_id: TypeId,
+    type_name: &'static str,
+    get: GetFn,
+    get_mut: GetMutFn,
+}
+
+impl FieldInfo {
+    pub(crate) fn new<T, F, G, M>(name: &'static str, get: G, get_mut: M) -> Self
+    where
+        T: Any,
+        F: Any,
+        G: Fn(&T) -> &F + 'static,
+        M: Fn(&mut T) -> &mut F + 'static,
+    {
+        Self {
+            name,
+            type_id: TypeId::of::<F>(),
+            type_name: type_name::<F>(),
+            get: Box::new(move |value| Some(get(value.downcast_ref()?) as &dyn Any)),
+            get_mut: Box::new(move |value| Some(get_mut(value.downcast_mut()?) as &mut dyn Any)),
+        }
+    }
+
+    /// 字段的名字
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// 字段的类型的[TypeId]
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// 字段的类型的名字
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// 获取value中这个字段的引用
+    ///
+    /// value的类型不对时返回[None]
+    pub fn get<'a>(&self, value: &'a dyn Any) -> Option<&'a dyn Any> {
+        (self.get)(value)
+    }
+
+    /// 获取value中这个字段的可变引用
+    ///
+    /// value的类型不对时返回[None]
+    pub fn get_mut<'a>(&self, value: &'a mut dyn Any) -> Option<&'a mut dyn Any> {
+        (self.get_mut)(value)
+    }
+}
+
+impl Debug for FieldInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FieldInfo")
+            .field("name", &self.name)
+            .field("type_name", &self.type_name)
+            .finish()
+    }
+}
@@ -0,0 +1,553 @@
+mod field;
+
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+    mem::size_of,
+};
+
+pub use field::FieldInfo;
+
+use crate::{
+    hierarchy::{Children, Parent},
+    scene::{EntityMap, SceneComponent, SceneFns},
+    storage::Entity,
+    tools::ResManager,
+    world::World,
+};
+
+type DebugFn = Box<dyn Fn(&dyn Any, &mut Formatter<'_>) -> std::fmt::Result>;
+type DefaultFn = Box<dyn Fn() -> Box<dyn Any>>;
+
+/// 一个类型在运行时的信息
+///
+/// 由[TypeRegistry::register]创建
+pub struct TypeInfo {
+    name: &'static str,
+    type_name: &'static str,
+    type_id: TypeId,
+    size: usize,
+    debug: Option<DebugFn>,
+    default: Option<DefaultFn>,
+    fields: Vec<FieldInfo>,
+    /// 保存和加载场景用的函数
+    pub(crate) scene: Option<SceneFns>,
+}
+
+impl TypeInfo {
+    fn new<T: Any>() -> Self {
+        Self {
+            name: type_name::<T>(),
+            type_name: type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            size: size_of::<T>(),
+            debug: None,
+            default: None,
+            fields: vec![],
+            scene: None,
+        }
+    }
+
+    /// 类型的名字
+    ///
+    /// 默认是[type_name],可以通过[TypeBuilder::name]修改
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// 类型本身的名字,即[type_name]
+    ///
+    /// 不会被[TypeBuilder::name]修改
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// 类型的大小
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// 是否注册了[Debug]
+    pub fn has_debug(&self) -> bool {
+        self.debug.is_some()
+    }
+
+    /// 通过注册的[Default]创建一个值
+    ///
+    /// 没有注册[Default]时返回[None]
+    pub fn default_value(&self) -> Option<Box<dyn Any>> {
+        self.default.as_ref().map(|default| default())
+    }
+
+    /// 所有注册了的字段,按照注册的顺序排列
+    pub fn fields(&self) -> &[FieldInfo] {
+        &self.fields
+    }
+
+    /// 名字为name的字段
+    pub fn field(&self, name: &str) -> Option<&FieldInfo> {
+        self.fields.iter().find(|field| field.name() == name)
+    }
+}
+
+impl Debug for TypeInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypeInfo")
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .field("debug", &self.debug.is_some())
+            .field("default", &self.default.is_some())
+            .field("fields", &self.fields)
+            .field("scene", &self.scene.is_some())
+            .finish()
+    }
+}
+
+/// 类型注册表,作为资源储存在[World]中
+///
+/// 记录[Component]和资源在运行时的信息,
+/// 使得只有[TypeId]时也能操作它们
+///
+/// 默认注册了[Parent]和[Children]
+///
+/// [Component]: crate::bundle::Component
+#[derive(Debug)]
+pub struct TypeRegistry {
+    types: HashMap<TypeId, TypeInfo>,
+    names: HashMap<&'static str, TypeId>,
+}
+
+impl TypeRegistry {
+    /// 创建一个空的[TypeRegistry]
+    pub fn new() -> Self {
+        Self {
+            types: Default::default(),
+            names: Default::default(),
+        }
+    }
+
+    /// 注册一个类型
+    ///
+    /// 如果类型已经注册过,返回的[TypeBuilder]会修改原来的信息
+    pub fn register<T: Any>(&mut self) -> TypeBuilder<'_, T> {
+        let type_id = TypeId::of::<T>();
+        if !self.types.contains_key(&type_id) {
+            let info = TypeInfo::new::<T>();
+            self.names.insert(info.name, type_id);
+            self.types.insert(type_id, info);
+        }
+        TypeBuilder {
+            registry: self,
+            _t: PhantomData,
+        }
+    }
+
+    pub fn get(&self, type_id: TypeId) -> Option<&TypeInfo> {
+        self.types.get(&type_id)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&TypeInfo> {
+        self.types.get(self.names.get(name)?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TypeInfo> {
+        self.types.values()
+    }
+
+    /// 通过[TypeRegistry]中的信息格式化value
+    ///
+    /// + 注册了[Debug]时使用[Debug]
+    /// + 否则注册了字段时,逐个格式化所有字段
+    /// + 否则只输出类型的名字
+    ///
+    /// 没有注册的类型会被格式化为"..."
+    pub fn format(&self, value: &dyn Any) -> String {
+        let Some(info) = self.get(value.type_id()) else {
+            return "...".to_owned();
+        };
+        if let Some(debug) = &info.debug {
+            return format!("{:?}", Formatted(value, debug));
+        }
+        if info.fields.is_empty() {
+            return info.name.to_owned();
+        }
+        let fields = info
+            .fields
+            .iter()
+            .filter_map(|field| Some((field.name(), self.format(field.get(value)?))))
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect::<Vec<_>>();
+        format!("{} {{ {} }}", info.name, fields.join(", "))
+    }
+}
+
+/// 用[DebugFn]实现[Debug]
+struct Formatted<'a>(&'a dyn Any, &'a DebugFn);
+
+impl Debug for Formatted<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        (self.1)(self.0, f)
+    }
+}
+
+impl Default for TypeRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register::<Entity>().debug();
+        registry.register::<Parent>().debug().scene();
+        registry.register::<Children>().debug().default().scene();
+        registry
+    }
+}
+
+/// 向[TypeRegistry]中的一个类型添加信息
+pub struct TypeBuilder<'a, T: Any> {
+    registry: &'a mut TypeRegistry,
+    _t: PhantomData<T>,
+}
+
+impl<T: Any> TypeBuilder<'_, T> {
+    fn info(&mut self) -> &mut TypeInfo {
+        self.registry.types.get_mut(&TypeId::of::<T>()).unwrap()
+    }
+
+    /// 修改类型的名字
+    ///
+    /// [type_name]并不保证稳定,需要长期保存的场景最好手动命名
+    pub fn name(mut self, name: &'static str) -> Self {
+        let old = std::mem::replace(&mut self.info().name, name);
+        self.registry.names.remove(old);
+        self.registry.names.insert(name, TypeId::of::<T>());
+        self
+    }
+
+    /// 注册[Debug],用于[TypeRegistry::format]
+    pub fn debug(mut self) -> Self
+    where
+        T: Debug,
+    {
+        self.info().debug = Some(Box::new(|value, f| {
+            value.downcast_ref::<T>().unwrap().fmt(f)
+        }));
+        self
+    }
+
+    /// 注册[Default],用于[TypeInfo::default_value]
+    pub fn default(mut self) -> Self
+    where
+        T: Default,
+    {
+        self.info().default = Some(Box::new(|| Box::<T>::default()));
+        self
+    }
+
+    /// 注册一个字段
+    ///
+    /// ``` ignore
+    /// registry
+    ///     .register::<Pos>()
+    ///     .field("x", |pos| &pos.x, |pos| &mut pos.x)
+    ///     .field("y", |pos| &pos.y, |pos| &mut pos.y);
+    /// ```
+    pub fn field<F, G, M>(mut self, name: &'static str, get: G, get_mut: M) -> Self
+    where
+        F: Any,
+        G: Fn(&T) -> &F + 'static,
+        M: Fn(&mut T) -> &mut F + 'static,
+    {
+        let fields = &mut self.info().fields;
+        fields.retain(|field| field.name() != name);
+        fields.push(FieldInfo::new(name, get, get_mut));
+        self
+    }
+
+    /// 使用[SceneComponent]保存和加载这个类型
+    pub fn scene(self) -> Self
+    where
+        T: SceneComponent,
+    {
+        self.scene_with(T::save, T::load)
+    }
+
+    /// 使用自定义的函数保存和加载这个类型
+    pub fn scene_with<S, L>(mut self, save: S, load: L) -> Self
+    where
+        S: Fn(&T, &EntityMap) -> String + 'static,
+        L: Fn(&str, &EntityMap) -> Option<T> + 'static,
+    {
+        self.info().scene = Some(SceneFns::new(save, load));
+        self
+    }
+}
+
+impl World {
+    /// 获取[TypeRegistry]资源
+    ///
+    /// 如果资源不存在,会创建一个[TypeRegistry::default]
+    pub fn type_registry(&mut self) -> &mut TypeRegistry {
+        self.new_res::<TypeRegistry>();
+        let res = self
+            .resources
+            .get_mut(&TypeId::of::<TypeRegistry>())
+            .unwrap()
+            .get_mut();
+        res.get_or_insert_with(|| Box::<TypeRegistry>::default())
+            .downcast_mut()
+            .unwrap()
+    }
+
+    /// 获取[TypeRegistry]资源的不可变引用
+    pub(crate) fn try_type_registry(&self) -> Option<&TypeRegistry> {
+        let res = self.resources.get(&TypeId::of::<TypeRegistry>())?;
+        // 只读,并且持有World的不可变引用,不会和Res冲突
+        unsafe { &*res.get() }.as_ref()?.downcast_ref()
+    }
+
+    /// 获取[Entity]上所有的[Component]
+    ///
+    /// 返回([TypeId],[Component]的引用),按照[Component]在[Entity]中的顺序排列
+    ///
+    /// [Entity]失效时返回[None]
+    ///
+    /// [Component]: crate::bundle::Component
+    pub fn reflect(&self, entity: Entity) -> Option<Vec<(TypeId, &dyn Any)>> {
+        let location = self.locate(entity)?;
+        let chunk = &self.chunks[location.chunk_index()];
+        let components = unsafe { chunk.get(location.index_in_chunk()) };
+        Some(
+            self.metas[chunk.meta()]
+                .components_ids
+                .iter()
+                .copied()
+                .zip(components.iter().map(|c| c.as_ref()))
+                .collect(),
+        )
+    }
+
+    /// 获取[Entity]上[TypeId]对应的[Component]的可变引用
+    ///
+    /// 配合[FieldInfo::get_mut]可以修改任意注册过的字段
+    ///
+    /// [Component]: crate::bundle::Component
+    pub fn reflect_mut(&mut self, entity: Entity, type_id: TypeId) -> Option<&mut dyn Any> {
+        let location = self.locate(entity)?;
+        let chunk = &mut self.chunks[location.chunk_index()];
+        let idx = self.metas[chunk.meta()]
+            .components_ids
+            .iter()
+            .position(|id| *id == type_id)?;
+        let components = unsafe { chunk.get_mut(location.index_in_chunk()) };
+        self.changes.mark_changed(entity, type_id);
+        Some(components[idx].as_mut())
+    }
+
+    /// 获取[Entity]上[TypeId]对应的[Component]中,名字为field的字段的可变引用
+    ///
+    /// 字段需要在[TypeRegistry]中注册过
+    ///
+    /// [Component]: crate::bundle::Component
+    pub fn reflect_field_mut(
+        &mut self,
+        entity: Entity,
+        type_id: TypeId,
+        field: &str,
+    ) -> Option<&mut dyn Any> {
+        let location = self.locate(entity)?;
+        let chunk = &mut self.chunks[location.chunk_index()];
+        let idx = self.metas[chunk.meta()]
+            .components_ids
+            .iter()
+            .position(|id| *id == type_id)?;
+        let components = unsafe { chunk.get_mut(location.index_in_chunk()) };
+        self.changes.mark_changed(entity, type_id);
+        // 资源和区块是分开存放的,不会冲突
+        let registry = self.resources.get(&TypeId::of::<TypeRegistry>())?;
+        let registry = unsafe { &*registry.get() }
+            .as_ref()?
+            .downcast_ref::<TypeRegistry>()?;
+        registry
+            .get(type_id)?
+            .field(field)?
+            .get_mut(components[idx].as_mut())
+    }
+
+    /// 获取[TypeId]对应的资源的可变引用
+    pub fn reflect_res(&mut self, type_id: TypeId) -> Option<&mut dyn Any> {
+        Some(
+            self.resources
+                .get_mut(&type_id)?
+                .get_mut()
+                .as_mut()?
+                .as_mut(),
+        )
+    }
+
+    /// 通过[TypeRegistry]格式化[Entity]上所有的[Component]
+    ///
+    /// 没有注册的[Component]会被显示为"..."
+    ///
+    /// [Entity]失效时返回[None]
+    ///
+    /// [Component]: crate::bundle::Component
+    pub fn inspect(&self, entity: Entity) -> Option<String> {
+        let default_registry;
+        let registry = match self.try_type_registry() {
+            Some(registry) => registry,
+            None => {
+                default_registry = TypeRegistry::default();
+                &default_registry
+            }
+        };
+        let components = self
+            .reflect(entity)?
+            .into_iter()
+            .map(|(type_id, component)| {
+                let name = registry.get(type_id).map(TypeInfo::name).unwrap_or("?");
+                format!("    {name}: {},\n", registry.format(component))
+            })
+            .collect::<String>();
+        Some(format!("{entity:?} {{\n{components}}}"))
+    }
+}
+
+/// [Component]通过[TypeRegistry]格式化,没有[TypeRegistry]时显示为"..."
+///
+/// [Component]: crate::bundle::Component
+impl Debug for World {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let registry = self.try_type_registry();
+        f.debug_struct("World")
+            .field(
+                "chunks",
+                &self
+                    .chunks
+                    .iter()
+                    .map(|chunk| chunk.debug(registry))
+                    .collect::<Vec<_>>(),
+            )
+            .field("relations", &self.relations)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bundle::Component, tools::Command};
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Pos {
+        x: i32,
+        y: i32,
+    }
+
+    impl Component for Pos {
+        fn type_id_() -> TypeId {
+            TypeId::of::<Self>()
+        }
+    }
+
+    struct Hidden;
+
+    impl Component for Hidden {
+        fn type_id_() -> TypeId {
+            TypeId::of::<Self>()
+        }
+    }
+
+    #[test]
+    fn register() {
+        let mut registry = TypeRegistry::new();
+        registry
+            .register::<Pos>()
+            .name("Pos")
+            .default()
+            .field("x", |pos| &pos.x, |pos| &mut pos.x)
+            .field("y", |pos| &pos.y, |pos| &mut pos.y);
+        registry.register::<i32>().debug();
+
+        let info = registry.get_by_name("Pos").unwrap();
+        assert_eq!(info.size(), size_of::<Pos>());
+        assert!(!info.has_debug());
+        assert_eq!(
+            info.fields()
+                .iter()
+                .map(FieldInfo::name)
+                .collect::<Vec<_>>(),
+            vec!["x", "y"]
+        );
+
+        let mut pos = info.default_value().unwrap();
+        *info
+            .field("y")
+            .unwrap()
+            .get_mut(pos.as_mut())
+            .unwrap()
+            .downcast_mut::<i32>()
+            .unwrap() = 2;
+        assert_eq!(pos.downcast_ref::<Pos>(), Some(&Pos { x: 0, y: 2 }));
+        // 没有注册Debug时,逐个格式化字段
+        assert_eq!(registry.format(pos.as_ref()), "Pos { x: 0, y: 2 }");
+
+        registry.register::<Pos>().debug();
+        assert_eq!(registry.format(pos.as_ref()), "Pos { x: 0, y: 2 }");
+        assert_eq!(registry.format(&Hidden), "...");
+    }
+
+    #[test]
+    fn inspect() {
+        let mut world = World::new();
+        world
+            .type_registry()
+            .register::<Pos>()
+            .name("Pos")
+            .field("x", |pos| &pos.x, |pos| &mut pos.x)
+            .field("y", |pos| &pos.y, |pos| &mut pos.y);
+        world.type_registry().register::<i32>().debug();
+
+        let entity = world.spawn((Pos { x: 1, y: 2 }, 3, Hidden));
+        assert_eq!(
+            world.inspect(entity).unwrap(),
+            format!("{entity:?} {{\n    Pos: Pos {{ x: 1, y: 2 }},\n    i32: 3,\n    ?: ...,\n}}")
+        );
+
+        // 只通过名字修改Component
+        let type_id = world.type_registry().get_by_name("Pos").unwrap().type_id();
+        let x = world.reflect_field_mut(entity, type_id, "x").unwrap();
+        *x.downcast_mut::<i32>().unwrap() = 5;
+        assert!(world.reflect_field_mut(entity, type_id, "z").is_none());
+        assert_eq!(world.get::<Pos>(entity), Some(&Pos { x: 5, y: 2 }));
+
+        *world
+            .reflect_mut(entity, TypeId::of::<i32>())
+            .unwrap()
+            .downcast_mut::<i32>()
+            .unwrap() = 4;
+        assert_eq!(world.get::<i32>(entity), Some(&4));
+
+        world.remove(entity);
+        assert!(world.inspect(entity).is_none());
+    }
+
+    #[test]
+    fn debug_chunk() {
+        let mut world = World::new();
+        world.spawn((Pos { x: 1, y: 2 }, Hidden));
+        // 没有TypeRegistry时只能显示"..."
+        assert!(format!("{world:?}").contains("bundles: {0: [..., ...]}"));
+
+        world.type_registry().register::<Pos>().debug();
+        assert!(format!("{world:?}").contains("bundles: {0: [Pos { x: 1, y: 2 }, ...]}"));
+        let chunk = &world.chunks[0];
+        assert!(format!("{chunk:?}").contains("bundles: {0: [..., ...]}"));
+        assert!(
+            format!("{:?}", chunk.debug(world.try_type_registry())).contains("Pos { x: 1, y: 2 }")
+        );
+    }
+}
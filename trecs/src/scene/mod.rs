@@ -0,0 +1,598 @@
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    bundle::Component,
+    hierarchy::{Children, Parent},
+    reflect::TypeRegistry,
+    storage::Entity,
+    world::{Composition, World},
+};
+
+/// 可以被保存到场景中的[Component]
+///
+/// 保存的结果是一行人类可读的文本,换行符等会被自动转义
+///
+/// 需要通过[TypeRegistry]注册后才会被保存和加载
+///
+/// ``` ignore
+/// world.type_registry().register::<MyComponent>().scene();
+/// ```
+pub trait SceneComponent: Component + Sized {
+    /// 保存为文本
+    ///
+    /// 其中的[Entity]需要通过[EntityMap::id]转换为场景中的编号
+    fn save(&self, entities: &EntityMap) -> String;
+
+    /// 从文本加载
+    ///
+    /// 其中的[Entity]需要通过[EntityMap::entity]从场景中的编号转换回来
+    ///
+    /// 失败时返回[None]
+    fn load(text: &str, entities: &EntityMap) -> Option<Self>;
+}
+
+type SaveFn = Box<dyn Fn(&dyn Any, &EntityMap) -> String>;
+type LoadFn = Box<dyn Fn(&str, &EntityMap) -> Option<Box<dyn Any>>>;
+
+/// [TypeRegistry]中保存的,类型擦除后的保存和加载函数
+pub(crate) struct SceneFns {
+    pub(crate) save: SaveFn,
+    pub(crate) load: LoadFn,
+}
+
+impl SceneFns {
+    pub(crate) fn new<T, S, L>(save: S, load: L) -> Self
+    where
+        T: Any,
+        S: Fn(&T, &EntityMap) -> String + 'static,
+        L: Fn(&str, &EntityMap) -> Option<T> + 'static,
+    {
+        Self {
+            save: Box::new(move |any, entities| save(any.downcast_ref().unwrap(), entities)),
+            load: Box::new(move |text, entities| {
+                load(text, entities).map(|t| Box::new(t) as Box<dyn Any>)
+            }),
+        }
+    }
+}
+
+/// 场景中的编号和[World]中的[Entity]之间的映射
+///
+/// + 保存时,通过[EntityMap::id]获取[Entity]在场景中的编号
+/// + 加载时,通过[EntityMap::entity]获取编号对应的新[Entity]
+#[derive(Debug, Default)]
+pub struct EntityMap {
+    ids: HashMap<Entity, usize>,
+    entities: HashMap<usize, Entity>,
+}
+
+impl EntityMap {
+    /// [Entity]在场景中的编号
+    ///
+    /// [Entity]不在场景中(比如已经失效)时返回[None]
+    pub fn id(&self, entity: Entity) -> Option<usize> {
+        self.ids.get(&entity).copied()
+    }
+
+    /// 场景中的编号对应的[Entity]
+    pub fn entity(&self, id: usize) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+}
+
+/// 保存或者加载场景时发生的错误
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    /// 无法解析的行
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    /// 没有在[TypeRegistry]中注册保存和加载函数的类型
+    UnknownType {
+        line: usize,
+        name: String,
+    },
+    /// [SceneComponent::load]失败
+    BadValue {
+        line: usize,
+        name: String,
+    },
+    /// 重复的[Entity]编号
+    DuplicateEntity {
+        line: usize,
+        id: usize,
+    },
+    /// 同一个[Entity]中重复的[Component]
+    DuplicateComponent {
+        line: usize,
+        name: String,
+    },
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "io error: {e}"),
+            SceneError::Syntax { line, text } => {
+                write!(f, "line {line}: cannot parse `{text}`")
+            }
+            SceneError::UnknownType { line, name } => {
+                write!(f, "line {line}: type `{name}` is not registered for scenes")
+            }
+            SceneError::BadValue { line, name } => {
+                write!(f, "line {line}: cannot load value of `{name}`")
+            }
+            SceneError::DuplicateEntity { line, id } => {
+                write!(f, "line {line}: entity {id} is defined twice")
+            }
+            SceneError::DuplicateComponent { line, name } => {
+                write!(f, "line {line}: component `{name}` is defined twice")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// 转义文本,使其只占一行
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// [escape]的逆操作
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// 场景中的一个[Entity]
+struct Record {
+    id: usize,
+    /// (行号,类型名,文本)
+    components: Vec<(usize, String, String)>,
+}
+
+/// 解析场景文本
+fn parse(text: &str) -> Result<Vec<Record>, SceneError> {
+    let mut records: Vec<Record> = vec![];
+    let mut ids = HashSet::new();
+
+    for (line, raw) in text.lines().enumerate() {
+        let line = line + 1;
+        let trimed = raw.trim();
+        if trimed.is_empty() || trimed.starts_with('#') {
+            continue;
+        }
+        let syntax_error = || SceneError::Syntax {
+            line,
+            text: raw.to_owned(),
+        };
+
+        if let Some(id) = trimed.strip_prefix("entity ") {
+            let id = id.trim().parse().map_err(|_| syntax_error())?;
+            if !ids.insert(id) {
+                return Err(SceneError::DuplicateEntity { line, id });
+            }
+            records.push(Record {
+                id,
+                components: vec![],
+            });
+            continue;
+        }
+
+        let (name, value) = raw.trim_start().split_once('=').ok_or_else(syntax_error)?;
+        let record = records.last_mut().ok_or_else(syntax_error)?;
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        record
+            .components
+            .push((line, name.trim().to_owned(), unescape(value)));
+    }
+
+    Ok(records)
+}
+
+impl World {
+    /// 将[World]中所有的[Entity]保存为场景
+    ///
+    /// 只有在[TypeRegistry]中注册了保存和加载函数的[Component]会被保存,
+    /// 其他[Component]会被忽略
+    ///
+    /// 格式如下,[Entity]按照被创建的位置排列,[Component]按照名字排列
+    ///
+    /// ``` text
+    /// entity 0
+    ///     trecs::hierarchy::Children = 1
+    /// entity 1
+    ///     i32 = 12345
+    ///     trecs::hierarchy::Parent = 0
+    /// ```
+    pub fn save_scene<W: Write>(&self, mut writer: W) -> Result<(), SceneError> {
+        let default_registry;
+        let registry = match self.try_type_registry() {
+            Some(registry) => registry,
+            None => {
+                default_registry = TypeRegistry::default();
+                &default_registry
+            }
+        };
+
+        // 按照Entity排序,这样被移动过的Entity也不会改变顺序
+        let mut slots = vec![];
+        for chunk in &self.chunks {
+            let mut iter = chunk.iter();
+            while let Some(components) = iter.next() {
+                slots.push((chunk.gen_entity(iter.index), chunk.meta(), components));
+            }
+        }
+        slots.sort_by_key(|(entity, ..)| entity.index);
+
+        // 先为所有Entity编号
+        let mut entities = EntityMap::default();
+        for (id, (entity, ..)) in slots.iter().enumerate() {
+            entities.ids.insert(*entity, id);
+        }
+
+        writeln!(writer, "# trecs scene")?;
+        for (id, (.., meta, components)) in slots.into_iter().enumerate() {
+            let mut lines = self.metas[meta]
+                .components_ids
+                .iter()
+                .zip(components)
+                .filter_map(|(type_id, component)| {
+                    let info = registry.get(*type_id)?;
+                    let save = &info.scene.as_ref()?.save;
+                    Some((info.name(), escape(&save(component.as_ref(), &entities))))
+                })
+                .collect::<Vec<_>>();
+            lines.sort();
+
+            writeln!(writer, "entity {id}")?;
+            for (name, value) in lines {
+                writeln!(writer, "    {name} = {value}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 从场景中加载[Entity]到[World]中
+    ///
+    /// 场景中对[Entity]的引用会被映射为新的[Entity]
+    ///
+    /// 返回按照场景中顺序排列的新[Entity],失败时不会改变[World]
+    pub fn load_scene<R: Read>(&mut self, mut reader: R) -> Result<Vec<Entity>, SceneError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let records = parse(&text)?;
+
+        // 加载时需要修改World,所以先把TypeRegistry拿出来
+        let registry = std::mem::take(self.type_registry());
+        let result = self.load_records(&registry, records);
+        *self.type_registry() = registry;
+        result
+    }
+
+    fn load_records(
+        &mut self,
+        registry: &TypeRegistry,
+        mut records: Vec<Record>,
+    ) -> Result<Vec<Entity>, SceneError> {
+        // 检查类型,并且根据类型提前占好位置,以便获取新的Entity
+        let mut entities = EntityMap::default();
+        let mut reserved = vec![];
+        for record in &mut records {
+            let mut composition = Composition::default();
+            for (line, name, ..) in &record.components {
+                let info = registry
+                    .get_by_name(name)
+                    .filter(|info| info.scene.is_some())
+                    .ok_or_else(|| SceneError::UnknownType {
+                        line: *line,
+                        name: name.clone(),
+                    })?;
+                if composition.ids.contains(&info.type_id()) {
+                    return Err(SceneError::DuplicateComponent {
+                        line: *line,
+                        name: name.clone(),
+                    });
+                }
+                composition.ids.push(info.type_id());
+            }
+            // 按照TypeId排序,同样的Component不论在文件中的顺序如何都属于同一个原型
+            record
+                .components
+                .sort_by_key(|(_, name, _)| registry.get_by_name(name).unwrap().type_id());
+            composition.ids.sort();
+            composition.names = composition
+                .ids
+                .iter()
+                .map(|id| registry.get(*id).unwrap().type_name())
+                .collect();
+            reserved.push(composition);
+        }
+
+        let new_entities = reserved
+            .iter()
+            .zip(&records)
+            .map(|(composition, record)| {
+                let meta = self.archetype_index(composition);
+                let entity = self.insert_components(meta, vec![]);
+                entities.entities.insert(record.id, entity);
+                entity
+            })
+            .collect::<Vec<_>>();
+
+        for (entity, record) in new_entities.iter().zip(&records) {
+            let components = record
+                .components
+                .iter()
+                .map(|(line, name, value)| {
+                    let load = &registry
+                        .get_by_name(name)
+                        .unwrap()
+                        .scene
+                        .as_ref()
+                        .unwrap()
+                        .load;
+                    load(value, &entities).ok_or_else(|| SceneError::BadValue {
+                        line: *line,
+                        name: name.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>();
+
+            match components {
+                Ok(components) => {
+                    let chunk = &mut self.chunks[entity.chunk_index()];
+                    chunk.put(entity.index_in_chunk(), components);
+                }
+                Err(e) => {
+                    // 撤销所有占好的位置
+                    for entity in &new_entities {
+                        self.despawn(*entity);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        self.spawned(&new_entities);
+        Ok(new_entities)
+    }
+}
+
+mod __impl {
+    use super::{EntityMap, SceneComponent};
+
+    macro_rules! impl_scene_component {
+        ($($t:ty),*) => {
+            $(impl SceneComponent for $t {
+                fn save(&self, _: &EntityMap) -> String {
+                    self.to_string()
+                }
+
+                fn load(text: &str, _: &EntityMap) -> Option<Self> {
+                    text.parse().ok()
+                }
+            })*
+        };
+    }
+
+    impl_scene_component!(u8, u16, u32, u64, usize, u128);
+    impl_scene_component!(i8, i16, i32, i64, isize, i128);
+    impl_scene_component!(bool);
+
+    impl SceneComponent for () {
+        fn save(&self, _: &EntityMap) -> String {
+            String::new()
+        }
+
+        fn load(text: &str, _: &EntityMap) -> Option<Self> {
+            text.is_empty().then_some(())
+        }
+    }
+}
+
+impl SceneComponent for Parent {
+    fn save(&self, entities: &EntityMap) -> String {
+        entities
+            .id(self.0)
+            .map(|id| id.to_string())
+            .unwrap_or_default()
+    }
+
+    fn load(text: &str, entities: &EntityMap) -> Option<Self> {
+        Some(Parent(entities.entity(text.parse().ok()?)?))
+    }
+}
+
+impl SceneComponent for Children {
+    fn save(&self, entities: &EntityMap) -> String {
+        self.0
+            .iter()
+            .filter_map(|entity| entities.id(*entity))
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn load(text: &str, entities: &EntityMap) -> Option<Self> {
+        text.split_whitespace()
+            .map(|id| entities.entity(id.parse().ok()?))
+            .collect::<Option<Vec<_>>>()
+            .map(Children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use super::*;
+    use crate::tools::Command;
+
+    struct Name(String);
+
+    impl Component for Name {
+        fn type_id_() -> TypeId {
+            TypeId::of::<Self>()
+        }
+    }
+
+    fn save(world: &World) -> String {
+        let mut buffer = vec![];
+        world.save_scene(&mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn save_load() {
+        let mut world = World::new();
+        world.type_registry().register::<i32>().scene();
+        world
+            .type_registry()
+            .register::<Name>()
+            .name("Name")
+            .scene_with(|n, _| n.0.clone(), |text, _| Some(Name(text.into())));
+
+        let tank = world.spawn((1, Name("tank\nA".into())));
+        let turret = world.spawn(2);
+        // 没有注册的Component不会被保存
+        world.spawn(3usize);
+        world.set_parent(turret, tank);
+
+        let text = save(&world);
+        assert_eq!(
+            text,
+            "# trecs scene
+entity 0
+    Name = tank\\nA
+    i32 = 1
+    trecs::hierarchy::Children = 1
+entity 1
+    i32 = 2
+    trecs::hierarchy::Parent = 0
+entity 2
+"
+        );
+
+        let mut loaded = World::new();
+        loaded.type_registry().register::<i32>().scene();
+        loaded
+            .type_registry()
+            .register::<Name>()
+            .name("Name")
+            .scene_with(|n, _| n.0.clone(), |text, _| Some(Name(text.into())));
+        // 放入一些别的Entity,使新的Entity和原来的不同
+        loaded.spawn_many(0..10);
+
+        let entities = loaded.load_scene(text.as_bytes()).unwrap();
+        assert_eq!(entities.len(), 3);
+        let (tank, turret) = (entities[0], entities[1]);
+        assert_eq!(loaded.get::<Name>(tank).unwrap().0, "tank\nA");
+        assert_eq!(loaded.get::<Parent>(turret).map(Parent::get), Some(tank));
+        assert_eq!(loaded.descendants(tank).collect::<Vec<_>>(), vec![turret]);
+
+        // 再次保存的结果是稳定的
+        let mut again = World::new();
+        again.type_registry().register::<i32>().scene();
+        again
+            .type_registry()
+            .register::<Name>()
+            .name("Name")
+            .scene_with(|n, _| n.0.clone(), |text, _| Some(Name(text.into())));
+        again.load_scene(text.as_bytes()).unwrap();
+        assert_eq!(save(&again), text);
+    }
+
+    #[test]
+    fn same_archetype() {
+        let mut world = World::new();
+        world.type_registry().register::<i32>().scene();
+        world
+            .type_registry()
+            .register::<Name>()
+            .name("Name")
+            .scene_with(|n, _| n.0.clone(), |text, _| Some(Name(text.into())));
+
+        // 文件中Component的顺序不同,但是属于同一个原型
+        let text = "# trecs scene
+entity 0
+    i32 = 1
+    Name = a
+entity 1
+    Name = b
+    i32 = 2
+";
+        let entities = world.load_scene(text.as_bytes()).unwrap();
+        let archetype = |e| world.entity(e).unwrap().archetype();
+        assert_eq!(archetype(entities[0]), archetype(entities[1]));
+        assert_eq!(world.get::<i32>(entities[1]), Some(&2));
+        assert_eq!(world.get::<Name>(entities[1]).unwrap().0, "b");
+    }
+
+    #[test]
+    fn errors() {
+        let mut world = World::new();
+        world.type_registry().register::<i32>().scene();
+
+        let unknown = "entity 0\n    u8 = 1\n";
+        assert!(matches!(
+            world.load_scene(unknown.as_bytes()),
+            Err(SceneError::UnknownType { line: 2, .. })
+        ));
+
+        let bad = "entity 0\n    i32 = 1\nentity 1\n    i32 = abc\n";
+        assert!(matches!(
+            world.load_scene(bad.as_bytes()),
+            Err(SceneError::BadValue { line: 4, .. })
+        ));
+        // 失败时不会留下任何Entity
+        assert_eq!(
+            crate::world::Query::<&i32>::new(&mut world)
+                .into_iter()
+                .count(),
+            0
+        );
+
+        let syntax = "    i32 = 1\n";
+        assert!(matches!(
+            world.load_scene(syntax.as_bytes()),
+            Err(SceneError::Syntax { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,318 @@
+use std::{any::TypeId, borrow::Cow, collections::HashMap, marker::PhantomData};
+
+use crate::{
+    bundle::Component,
+    iter::{EIter, Iter},
+    storage::Entity,
+    tools::ResManager,
+    world::{Query, World},
+};
+
+/// 可以作为[SpatialIndex]的键的[Component]
+///
+/// ```ignore
+/// impl Position for Pos {
+///     fn position(&self) -> [f32; 2] {
+///         [self.x, self.y]
+///     }
+/// }
+/// ```
+pub trait Position: Component {
+    fn position(&self) -> [f32; 2];
+}
+
+/// 网格的坐标
+type Cell = (i32, i32);
+
+/// 以[Position]为键的均匀网格
+///
+/// 作为资源储存在[World]中,通过[World::add_spatial_index]添加
+///
+/// 更新时只会检查可能变化了的[Entity]:
+///
+/// + 通过get_mut,[EntityMut::insert]等修改过,新增或删除的[Entity]
+/// + 可写的[Query]匹配的原型中的所有[Entity]
+///
+/// [EntityMut::insert]: crate::world::EntityMut::insert
+pub struct SpatialIndex<P: Position> {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Entity>>,
+    /// 每个[Entity]上一次的位置,以及所在的网格
+    positions: HashMap<Entity, ([f32; 2], Cell)>,
+    _p: PhantomData<fn() -> P>,
+}
+
+impl<P: Position> SpatialIndex<P> {
+    /// cell_size是网格的边长,最好和常用的查询半径差不多
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cell_size must be positive");
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+            _p: PhantomData,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn cell(&self, [x, y]: [f32; 2]) -> Cell {
+        (
+            (x / self.cell_size).floor() as i32,
+            (y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// 上一次更新时[Entity]的位置
+    pub fn position(&self, entity: Entity) -> Option<[f32; 2]> {
+        self.positions.get(&entity).map(|(position, _)| *position)
+    }
+
+    /// 索引中[Entity]的数量
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// 所有到point的距离不超过r的[Entity]
+    ///
+    /// 只会检查和圆相交的网格,可以和[Query::get]一起使用
+    ///
+    /// 圆覆盖的网格比非空的网格还多时,改为检查所有非空的网格
+    ///
+    /// point或r不是有限的数,或者r为负数时会panic
+    pub fn within_radius(&self, point: [f32; 2], r: f32) -> impl Iterator<Item = Entity> + '_ {
+        assert!(
+            point.iter().all(|x| x.is_finite()) && r.is_finite() && r >= 0.0,
+            "within_radius needs a finite point and a finite, non-negative radius"
+        );
+        let (min_x, min_y) = self.cell([point[0] - r, point[1] - r]);
+        let (max_x, max_y) = self.cell([point[0] + r, point[1] + r]);
+        let covered =
+            (max_x as i64 - min_x as i64 + 1).saturating_mul(max_y as i64 - min_y as i64 + 1);
+        let cells: Vec<&Vec<Entity>> = if covered <= self.cells.len() as i64 {
+            (min_x..=max_x)
+                .flat_map(|x| (min_y..=max_y).map(move |y| (x, y)))
+                .filter_map(|cell| self.cells.get(&cell))
+                .collect()
+        } else {
+            self.cells
+                .iter()
+                .filter(|((x, y), _)| (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y))
+                .map(|(_, entities)| entities)
+                .collect()
+        };
+        cells.into_iter().flatten().copied().filter(move |entity| {
+            let [x, y] = self.positions[entity].0;
+            let (dx, dy) = (x - point[0], y - point[1]);
+            dx * dx + dy * dy <= r * r
+        })
+    }
+
+    /// 和[World]中的[Position]同步
+    ///
+    /// 只会检查上一次更新之后可能变化了的[Entity]
+    ///
+    /// + 位置变化的[Entity]会移动到新的网格
+    /// + 不再拥有[Position]的[Entity]会被移除
+    pub fn update(&mut self, world: &mut World) {
+        let changed = world.changes.take(TypeId::of::<Self>());
+        for meta in changed.archetypes {
+            let Some(mapping_table) = world.metas[meta].fetch::<&P>() else {
+                continue;
+            };
+            let iter = Iter::<&P>::from_matched(world, Cow::Owned(vec![(meta, mapping_table)]));
+            for e in EIter::from(iter) {
+                self.refresh(e.entity(), Some(e.position()));
+            }
+        }
+        for entity in changed.entities {
+            self.refresh(entity, world.get::<P>(entity).map(P::position));
+        }
+    }
+
+    /// 所有拥有[Position]的[Entity]
+    fn rebuild(&mut self, world: &mut World) {
+        for e in EIter::from(Query::<&P>::new(world).into_iter()) {
+            self.refresh(e.entity(), Some(e.position()));
+        }
+    }
+
+    /// 把[Entity]移动到position所在的网格,[None]时从索引中移除
+    fn refresh(&mut self, entity: Entity, position: Option<[f32; 2]>) {
+        let Some(position) = position else {
+            if let Some((_, cell)) = self.positions.remove(&entity) {
+                self.remove_from_cell(entity, cell);
+            }
+            return;
+        };
+        let cell = self.cell(position);
+        match self.positions.insert(entity, (position, cell)) {
+            Some((_, old_cell)) if old_cell == cell => {}
+            Some((_, old_cell)) => {
+                self.remove_from_cell(entity, old_cell);
+                self.cells.entry(cell).or_default().push(entity);
+            }
+            None => self.cells.entry(cell).or_default().push(entity),
+        }
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, cell: Cell) {
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.retain(|e| *e != entity);
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+}
+
+impl World {
+    /// 添加以P为键的[SpatialIndex]资源
+    ///
+    /// 会立即为已经存在的[Entity]建立索引,之后在每次[World::maintain]时更新
+    ///
+    /// 已经存在时什么都不做
+    pub fn add_spatial_index<P: Position>(&mut self, cell_size: f32) -> &mut Self {
+        if self.get_res::<SpatialIndex<P>>().get().is_none() {
+            self.changes
+                .track(TypeId::of::<SpatialIndex<P>>(), P::type_id_());
+            let mut index = SpatialIndex::<P>::new(cell_size);
+            index.rebuild(self);
+            self.get_res().get_or_init(|| index);
+            self.maintainers.push(World::update_spatial_index::<P>);
+        }
+        self
+    }
+
+    /// 立即更新以P为键的[SpatialIndex]
+    ///
+    /// 没有添加索引时什么都不做
+    pub fn update_spatial_index<P: Position>(&mut self) {
+        let Some(mut index) = self.get_res::<SpatialIndex<P>>().take() else {
+            return;
+        };
+        index.update(self);
+        self.get_res().get_or_init(|| *index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use super::{Position, SpatialIndex};
+    use crate::{
+        bundle::Component,
+        tools::{Command, ResManager},
+        world::Query,
+        World,
+    };
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Pos(f32, f32);
+
+    impl Component for Pos {
+        fn type_id_() -> TypeId {
+            TypeId::of::<Self>()
+        }
+    }
+
+    impl Position for Pos {
+        fn position(&self) -> [f32; 2] {
+            [self.0, self.1]
+        }
+    }
+
+    fn within(world: &mut World, point: [f32; 2], r: f32) -> Vec<crate::storage::Entity> {
+        let res = world.get_res::<SpatialIndex<Pos>>();
+        let mut entities = res
+            .get()
+            .unwrap()
+            .within_radius(point, r)
+            .collect::<Vec<_>>();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn spatial_index() {
+        let mut world = World::new();
+        world.add_spatial_index::<Pos>(2.0);
+        let e1 = world.spawn(Pos(0.0, 0.0));
+        let e2 = world.spawn((Pos(1.0, 1.0), 1u8));
+        let e3 = world.spawn(Pos(10.0, -3.0));
+        world.maintain();
+
+        assert_eq!(within(&mut world, [0.0, 0.0], 1.5), {
+            let mut expected = vec![e1, e2];
+            expected.sort();
+            expected
+        });
+        assert_eq!(within(&mut world, [9.0, -3.0], 1.0), vec![e3]);
+
+        // 位置变化后移动到新的网格
+        *world.get_mut::<Pos>(e1).unwrap() = Pos(9.5, -2.5);
+        world.remove(e3);
+        world.maintain();
+        assert_eq!(within(&mut world, [9.0, -3.0], 1.0), vec![e1]);
+        assert_eq!(within(&mut world, [0.0, 0.0], 1.5), vec![e2]);
+
+        // 和Query::get一起使用
+        let nearby = within(&mut world, [1.0, 1.0], 0.5);
+        let query = Query::<&u8>::new(&mut world);
+        assert_eq!(query.get(nearby[0]), Some(&1));
+        assert_eq!(query.get(e1), None);
+    }
+
+    #[test]
+    fn changes() {
+        let mut world = World::new();
+        let e1 = world.spawn(Pos(0.0, 0.0));
+        let e2 = world.spawn((Pos(0.0, 0.0), 1u8));
+        world.add_spatial_index::<Pos>(1.0);
+        assert_eq!(within(&mut world, [0.0, 0.0], 0.5).len(), 2);
+
+        // 可写的Query会记录匹配的原型
+        for pos in Query::<&mut Pos, crate::tools::With<u8>>::new(&mut world).iter_mut() {
+            *pos = Pos(5.0, 5.0);
+        }
+        world.entity_mut(e1).unwrap().insert(Pos(-5.0, -5.0));
+        let changed = world
+            .changes
+            .take(std::any::TypeId::of::<SpatialIndex<Pos>>());
+        assert_eq!(changed.archetypes.len(), 1);
+        assert!(changed.entities.contains(&e1));
+        assert!(!changed.entities.contains(&e2));
+
+        for pos in Query::<&mut Pos>::new(&mut world).iter_mut() {
+            pos.0 += 1.0;
+        }
+        world.maintain();
+        assert_eq!(within(&mut world, [6.0, 5.0], 0.5), vec![e2]);
+        assert_eq!(within(&mut world, [-4.0, -5.0], 0.5), vec![e1]);
+
+        // 只读的Query不会记录变化
+        Query::<&Pos>::new(&mut world).iter().count();
+        let changed = world
+            .changes
+            .take(std::any::TypeId::of::<SpatialIndex<Pos>>());
+        assert!(changed.archetypes.is_empty() && changed.entities.is_empty());
+
+        // 半径很大时检查所有非空的网格
+        assert_eq!(within(&mut world, [0.0, 0.0], 1e30).len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn infinite_radius() {
+        let mut world = World::new();
+        world.add_spatial_index::<Pos>(1.0);
+        within(&mut world, [0.0, 0.0], f32::INFINITY);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use super::Entity;
+
+/// [Chunk]中被移走和移过来的[Components]
+///
+/// 增删[Component]时[Entity]的[Components]会移动到另一个[BundleMeta]的[Chunk]中:
+///
+/// + 原来的位置保持"存活"并记录新的位置,这样原来的[Entity]就不会失效
+/// + 新的位置记录原来的[Entity],遍历时生成的仍然是原来的[Entity]
+///
+/// 再次移动时只更新原来位置的记录,中途经过的位置会被直接释放,
+/// 所以最多只需要跳转一次
+///
+/// [Chunk]:super::Chunk
+/// [Components]:crate::bundle::Components
+/// [Component]:crate::bundle::Component
+/// [BundleMeta]:crate::bundle::BundleMeta
+#[derive(Debug, Default)]
+pub(crate) struct Forwards {
+    /// 已经被移动到别处的位置,值为[Components]实际所在的位置
+    ///
+    /// [Components]:crate::bundle::Components
+    forwards: HashMap<usize, Entity>,
+    /// 从别处移动过来的位置,值为[Components]原本对应的[Entity]
+    ///
+    /// [Components]:crate::bundle::Components
+    owners: HashMap<usize, Entity>,
+}
+
+impl Forwards {
+    /// 记录对应位置的[Components]已经被移动到了location
+    ///
+    /// [Components]:crate::bundle::Components
+    pub fn forward(&mut self, index: usize, location: Entity) {
+        self.forwards.insert(index, location);
+    }
+
+    /// 对应位置的[Components]被移动到了哪里
+    ///
+    /// [Components]:crate::bundle::Components
+    pub fn forwarded(&self, index: usize) -> Option<Entity> {
+        self.forwards.get(&index).copied()
+    }
+
+    /// 记录对应位置的[Components]原本属于owner
+    ///
+    /// [Components]:crate::bundle::Components
+    pub fn own(&mut self, index: usize, owner: Entity) {
+        self.owners.insert(index, owner);
+    }
+
+    /// 对应位置的[Components]原本属于哪个[Entity]
+    ///
+    /// [Components]:crate::bundle::Components
+    pub fn owner(&self, index: usize) -> Option<Entity> {
+        self.owners.get(&index).copied()
+    }
+
+    /// 位置被释放时清除它的记录
+    pub fn remove(&mut self, index: usize) {
+        self.forwards.remove(&index);
+        self.owners.remove(&index);
+    }
+
+    /// 被移走的位置的数量
+    pub fn forwarded_count(&self) -> usize {
+        self.forwards.len()
+    }
+
+    pub fn forwards(&self) -> &HashMap<usize, Entity> {
+        &self.forwards
+    }
+
+    pub fn owners(&self) -> &HashMap<usize, Entity> {
+        &self.owners
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Forwards;
+    use crate::storage::{Entity, ALIVE_TAG};
+
+    #[test]
+    fn forwards() {
+        let mut forwards = Forwards::default();
+        let owner = Entity::new(ALIVE_TAG, 3);
+        let location = Entity::new(ALIVE_TAG, 2048);
+
+        forwards.forward(3, location);
+        forwards.own(0, owner);
+        assert_eq!(forwards.forwarded(3), Some(location));
+        assert_eq!(forwards.owner(0), Some(owner));
+        assert_eq!(forwards.forwarded_count(), 1);
+
+        forwards.remove(3);
+        forwards.remove(0);
+        assert_eq!(forwards.forwarded(3), None);
+        assert_eq!(forwards.owner(0), None);
+        assert_eq!(forwards.forwarded_count(), 0);
+    }
+}
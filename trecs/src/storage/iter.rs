@@ -1,4 +1,4 @@
-use crate::{bundle::Components, storage::ALIVE_TAG};
+use crate::bundle::Components;
 
 use super::Chunk;
 
@@ -28,7 +28,10 @@ impl<'a> Iterator for ChunkIter<'a> {
         } else {
             self.first = false;
         }
-        while self.chunk.alive.get(self.index).copied()? < ALIVE_TAG {
+        while !self.chunk.occupied(self.index) {
+            if self.index >= self.chunk.len() {
+                return None;
+            }
             self.index += 1;
         }
         Some(&self.chunk.bundles[self.index])
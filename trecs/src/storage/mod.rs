@@ -1,11 +1,16 @@
 mod entity;
+mod forward;
 mod iter;
 
 pub use entity::Entity;
+pub(crate) use forward::Forwards;
 pub(crate) use iter::ChunkIter;
 
-use crate::bundle::{Bundle, Components};
-use std::{any::TypeId, fmt::Debug};
+use crate::{
+    bundle::{Bundle, Components},
+    reflect::TypeRegistry,
+};
+use std::{collections::BTreeMap, fmt::Debug};
 
 /// 一个[Chunk]的大小
 ///
@@ -51,22 +56,27 @@ pub(crate) struct Chunk {
     ///
     /// 以便直接生成[Entity]
     index: usize,
-    /// 内部存储的[Bundle]的[TypeId]
+    /// 内部存储的[Bundle]的[BundleMeta]在[World]中的下标
     ///
     /// 用于直接使用[WorldFetch]
     ///
+    /// [BundleMeta]:crate
+    /// [World]:crate
     /// [WorldFetch]:crate
-    bundle_id: TypeId,
+    meta: usize,
+    /// 被移走和移过来的[Components]
+    forwards: Forwards,
 }
 
 impl Chunk {
-    pub fn new<B: Bundle>(idx: usize) -> Self {
+    pub fn new(idx: usize, meta: usize) -> Self {
         Self {
             bundles: Vec::with_capacity(CHUNK_SIZE),
             alive: Vec::with_capacity(CHUNK_SIZE),
             removed: vec![],
             index: idx,
-            bundle_id: B::type_id_(),
+            meta,
+            forwards: Default::default(),
         }
     }
 
@@ -76,8 +86,18 @@ impl Chunk {
     ///
     /// + 失败则原路返回[Bundle]
     pub fn insert<B: Bundle>(&mut self, b: B) -> Result<Entity, B> {
+        if self.free() == 0 {
+            return Err(b);
+        }
+        Ok(self.insert_components(b.destory()).unwrap())
+    }
+
+    /// 尝试在[Chunk]中插入已经拆开的[Components]
+    ///
+    /// 和[Chunk::insert]一样,失败时原路返回[Components]
+    pub fn insert_components(&mut self, cs: Components) -> Result<Entity, Components> {
         if self.bundles.len() != CHUNK_SIZE {
-            self.bundles.push(b.destory());
+            self.bundles.push(cs);
             self.alive.push(ALIVE_TAG);
             return Ok(Entity::new(
                 ALIVE_TAG,
@@ -87,11 +107,11 @@ impl Chunk {
 
         match self.removed.pop() {
             Some(slot) => {
-                self.bundles[slot] = b.destory();
+                self.bundles[slot] = cs;
                 self.alive[slot] += ALIVE_TAG + 1;
                 Ok(self.gen_entity(slot))
             }
-            None => Err(b),
+            None => Err(cs),
         }
     }
 
@@ -108,9 +128,37 @@ impl Chunk {
         self.bundles[index].clear();
         self.alive[index] -= ALIVE_TAG;
         self.removed.push(index);
+        self.forwards.remove(index);
         true
     }
 
+    /// 取出对应位置的[Components],但是不释放这个位置
+    ///
+    /// 用于移动[Components]
+    pub fn take(&mut self, index: usize) -> Components {
+        std::mem::take(&mut self.bundles[index])
+    }
+
+    /// 将[Components]放回由[Chunk::take]取空的位置
+    pub fn put(&mut self, index: usize, cs: Components) {
+        self.bundles[index] = cs;
+    }
+
+    /// 记录对应位置的[Components]已经被移动到了location
+    pub fn forward(&mut self, index: usize, location: Entity) {
+        self.forwards.forward(index, location);
+    }
+
+    /// 对应位置的[Components]被移动到了哪里
+    pub fn forwarded(&self, index: usize) -> Option<Entity> {
+        self.forwards.forwarded(index)
+    }
+
+    /// 记录对应位置的[Components]原本属于owner
+    pub fn own(&mut self, index: usize, owner: Entity) {
+        self.forwards.own(index, owner);
+    }
+
     /// 计算Entity是否有效
     ///
     /// + 返回[Some(bool)]时,[bool]表示[Entity]是否有效
@@ -124,13 +172,43 @@ impl Chunk {
         Some(*self.alive.get(index)? == entity.generator)
     }
 
+    /// 对应位置是否有可以访问的[Components]
+    ///
+    /// 被移走的位置虽然"存活",但是里面没有[Components]
+    pub fn occupied(&self, index: usize) -> bool {
+        match self.alive.get(index) {
+            Some(&alive) => {
+                alive >= ALIVE_TAG
+                    && !(self.bundles[index].is_empty() && self.forwarded(index).is_some())
+            }
+            None => false,
+        }
+    }
+
+    /// 可以访问的[Components]的数量
+    ///
+    /// 被删除的位置和被移走的位置都不算在内
+    pub fn count(&self) -> usize {
+        self.bundles.len() - self.removed.len() - self.forwards.forwarded_count()
+    }
+
+    /// 已经使用过的位置的数量,包括被删除的位置
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
     /// 空闲空间的长度
     pub fn free(&self) -> usize {
         CHUNK_SIZE - self.bundles.len() + self.removed.len()
     }
 
+    /// 生成对应位置的[Entity]
+    ///
+    /// 如果[Components]是从别处移动过来的,会返回原来的[Entity]
     pub fn gen_entity(&self, index: usize) -> Entity {
-        Entity::new(self.alive[index], self.index * CHUNK_SIZE + index)
+        self.forwards
+            .owner(index)
+            .unwrap_or_else(|| Entity::new(self.alive[index], self.index * CHUNK_SIZE + index))
     }
 
     /// 仅仅做引用的迭代器
@@ -145,7 +223,7 @@ impl Chunk {
         self.bundles
             .iter_mut()
             .enumerate()
-            .filter(|(id, ..)| self.alive[*id] > ALIVE_TAG)
+            .filter(|(id, cs)| self.alive[*id] >= ALIVE_TAG && !cs.is_empty())
             .for_each(|(.., cs)| {
                 let mut target = Vec::with_capacity(cs.len());
                 target.append(cs);
@@ -158,8 +236,89 @@ impl Chunk {
         &self.bundles[index]
     }
 
-    pub fn bundle_id(&self) -> TypeId {
-        self.bundle_id
+    /// 直接获取[Entity]对应的[Components]的可变引用
+    pub unsafe fn get_mut(&mut self, index: usize) -> &mut Components {
+        &mut self.bundles[index]
+    }
+
+    /// 对应位置的[Components]的指针
+    ///
+    /// 不会借用其他位置的[Components],可以同时持有不同位置的可变引用
+    pub fn components_ptr(&mut self, index: usize) -> *mut Components {
+        assert!(index < self.bundles.len());
+        // as_mut_ptr不会创建整个切片的引用
+        unsafe { self.bundles.as_mut_ptr().add(index) }
+    }
+
+    pub fn meta(&self) -> usize {
+        self.meta
+    }
+}
+
+impl Chunk {
+    /// 通过[TypeRegistry]格式化[Chunk]中的[Component]
+    ///
+    /// [Component]:crate
+    pub fn debug<'a>(&'a self, registry: Option<&'a TypeRegistry>) -> ChunkDebug<'a> {
+        ChunkDebug {
+            chunk: self,
+            registry,
+        }
+    }
+}
+
+/// 由[Chunk::debug]创建
+///
+/// 没有[TypeRegistry]或者没有注册过的[Component]会被显示为"..."
+///
+/// [Component]:crate
+pub(crate) struct ChunkDebug<'a> {
+    chunk: &'a Chunk,
+    registry: Option<&'a TypeRegistry>,
+}
+
+/// 不带引号输出的字符串
+struct Raw(String);
+
+impl Debug for Raw {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Debug for ChunkDebug<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chunk = self.chunk;
+        let bundles = (0..chunk.len())
+            .filter(|index| chunk.occupied(*index))
+            .map(|index| {
+                let components = chunk.bundles[index]
+                    .iter()
+                    .map(|component| match self.registry {
+                        Some(registry) => Raw(registry.format(component.as_ref())),
+                        None => Raw("...".to_owned()),
+                    })
+                    .collect::<Vec<_>>();
+                (index, components)
+            })
+            .collect::<BTreeMap<_, _>>();
+        f.debug_struct("Chunk")
+            .field("bundles", &bundles)
+            .field("alive", &chunk.alive)
+            .field("removed", &chunk.removed)
+            .field("index", &chunk.index)
+            .field("forwards", chunk.forwards.forwards())
+            .field("owners", chunk.forwards.owners())
+            .finish()
+    }
+}
+
+/// [Component]需要通过[TypeRegistry]格式化,见[Chunk::debug]
+///
+/// [Component]:crate
+impl Debug for Chunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.debug(None).fmt(f)
     }
 }
 
@@ -171,7 +330,7 @@ mod tests {
     fn insert_remove() {
         // hso
         // 这个（）是不是代表着什么呢？
-        let mut chunk = Chunk::new::<()>(0);
+        let mut chunk = Chunk::new(0, 0);
 
         // 先插进去两个
         assert_eq!(chunk.insert(123), Ok(Entity::new(ALIVE_TAG, 0)));
@@ -192,16 +351,3 @@ mod tests {
         assert_eq!(chunk.insert(114514), Ok(Entity::new(ALIVE_TAG + 1, 1)))
     }
 }
-
-impl Debug for Chunk {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Chunk")
-            // 因为Component  不能 Debug
-            // .field("bundles", &self.bundles)
-            .field("bundles", &"...")
-            .field("alive", &self.alive)
-            .field("removed", &self.removed)
-            .field("index", &self.index)
-            .finish()
-    }
-}
@@ -0,0 +1,225 @@
+use std::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    ptr::null_mut,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+use crate::World;
+
+/// 跨越多次循环的异步任务
+pub(crate) type AsyncTask = Pin<Box<dyn Future<Output = ()>>>;
+
+/// 所有[AsyncContext]共享的状态
+#[derive(Debug)]
+pub(crate) struct Shared {
+    /// 只在轮询异步任务时不为空
+    world: Cell<*mut World>,
+    /// 已经轮询了多少轮异步任务
+    frame: Cell<u64>,
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self {
+            world: Cell::new(null_mut()),
+            frame: Cell::new(0),
+        }
+    }
+}
+
+/// 通过[World::spawn_async]创建的异步任务的参数
+///
+/// 任务不直接持有[World]中的数据,每次需要时都通过[AsyncContext::with_world]重新获取,
+/// 所以任务挂起时其他[System]可以正常执行
+///
+/// ```ignore
+/// world.spawn_async(|ctx| async move {
+///     for _ in 0..3 {
+///         ctx.next_frame().await;
+///     }
+///     ctx.with_world(|world| world.spawn(Enemy));
+/// });
+/// ```
+///
+/// [System]: crate::system::System
+#[derive(Debug, Clone)]
+pub struct AsyncContext {
+    shared: Rc<Shared>,
+}
+
+impl AsyncContext {
+    /// 访问[World]
+    ///
+    /// 只能在任务中调用,不能嵌套调用
+    ///
+    /// f中调用[World::run_once]会panic
+    pub fn with_world<R>(&self, f: impl FnOnce(&mut World) -> R) -> R {
+        let world = self.shared.world.replace(null_mut());
+        assert!(
+            !world.is_null(),
+            "the world can only be accessed once at a time inside an async task"
+        );
+
+        /// f发生panic时也要把指针放回去
+        struct Restore<'a>(&'a Shared, *mut World);
+        impl Drop for Restore<'_> {
+            fn drop(&mut self) {
+                self.0.world.set(self.1);
+            }
+        }
+        let _restore = Restore(&self.shared, world);
+
+        // 轮询期间World被World::poll_async_tasks独占
+        f(unsafe { &mut *world })
+    }
+
+    /// 等待到下一次[World::run_once]
+    pub fn next_frame(&self) -> NextFrame {
+        NextFrame {
+            shared: self.shared.clone(),
+            frame: self.shared.frame.get(),
+        }
+    }
+}
+
+/// 由[AsyncContext::next_frame]创建
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct NextFrame {
+    shared: Rc<Shared>,
+    frame: u64,
+}
+
+impl Future for NextFrame {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        // 每次轮询的间隔都是一次循环,不需要唤醒
+        if self.shared.frame.get() > self.frame {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl World {
+    /// 添加一个可以跨越多次循环的异步任务
+    ///
+    /// 任务会在之后每次[World::run_once]执行完所有[System]后轮询一次,直到完成
+    ///
+    /// [System]: crate::system::System
+    pub fn spawn_async<F, Fut>(&mut self, task: F) -> &mut Self
+    where
+        F: FnOnce(AsyncContext) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let ctx = AsyncContext {
+            shared: self.async_shared.clone(),
+        };
+        self.async_tasks.push(Box::pin(task(ctx)));
+        self
+    }
+
+    /// 还没有完成的异步任务的数量
+    pub fn async_tasks(&self) -> usize {
+        self.async_tasks.len()
+    }
+
+    /// 轮询所有异步任务一次,删除已经完成的任务
+    pub(crate) fn poll_async_tasks(&mut self) {
+        if self.async_tasks.is_empty() {
+            return;
+        }
+        let shared = self.async_shared.clone();
+        shared.frame.set(shared.frame.get() + 1);
+
+        let mut tasks = std::mem::take(&mut self.async_tasks);
+        // 和执行System时一样,禁止在任务中调用World::run_once
+        self.running = true;
+        shared.world.set(self);
+        let mut cx = Context::from_waker(Waker::noop());
+        tasks.retain_mut(|task| task.as_mut().poll(&mut cx).is_pending());
+        shared.world.set(null_mut());
+        self.running = false;
+
+        // 轮询时新添加的任务
+        tasks.append(&mut self.async_tasks);
+        self.async_tasks = tasks;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tools::{Command, ResManager},
+        world::{Query, Res},
+        World,
+    };
+
+    #[test]
+    fn multi_frame_task() {
+        fn tick(mut ticks: Res<u32>) {
+            *ticks.get_mut().unwrap() += 1;
+        }
+
+        let mut world = World::new();
+        world.get_res::<u32>().get_or_init(|| 0);
+        world.add_system(tick);
+        world.spawn_async(|ctx| async move {
+            for _ in 0..3 {
+                ctx.next_frame().await;
+            }
+            ctx.with_world(|world| {
+                world.spawn(1usize);
+                world.spawn_async(|ctx| async move {
+                    ctx.with_world(|world| world.spawn(2usize));
+                });
+            });
+        });
+
+        for _ in 0..3 {
+            world.run_once();
+        }
+        // 任务挂起时System照常执行
+        assert_eq!(world.get_res::<u32>().get(), Some(&3));
+        assert!(Query::<&usize>::new(&mut world).is_empty());
+
+        world.run_once();
+        assert_eq!(
+            Query::<&usize>::new(&mut world).into_iter().sum::<usize>(),
+            1
+        );
+        assert_eq!(world.async_tasks(), 1);
+
+        world.run_once();
+        assert_eq!(
+            Query::<&usize>::new(&mut world).into_iter().sum::<usize>(),
+            3
+        );
+        assert_eq!(world.async_tasks(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "inside a running system")]
+    fn run_once_in_task() {
+        let mut world = World::new();
+        world.spawn_async(|ctx| async move {
+            ctx.with_world(|world| world.run_once());
+        });
+        world.run_once();
+    }
+
+    #[test]
+    #[should_panic]
+    fn nested_with_world() {
+        let mut world = World::new();
+        world.spawn_async(|ctx| async move {
+            ctx.with_world(|_| ctx.with_world(|_| {}));
+        });
+        world.run_once();
+    }
+}
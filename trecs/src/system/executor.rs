@@ -0,0 +1,101 @@
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+/// 唤醒时恢复被挂起的线程
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// 在当前线程上执行[Future]直到完成
+///
+/// 没有完成时挂起线程,等待[Waker]唤醒
+///
+/// [World::run_once]用它来执行异步系统
+///
+/// [World::run_once]: crate::World::run_once
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use super::block_on;
+    use crate::{
+        tools::{Command, ResManager},
+        world::{Query, Res},
+        World,
+    };
+
+    /// 第一次poll时返回Pending并立即唤醒
+    struct YieldNow(bool);
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn mixed_systems() {
+        fn sync_double(mut query: Query<&mut usize>) {
+            query.iter_mut().for_each(|value| *value *= 2);
+        }
+
+        async fn async_sum(query: Query<'_, &usize>, mut sum: Res<'_, usize>) {
+            YieldNow(false).await;
+            let total = query.into_iter().sum::<usize>();
+            *sum.get_mut().unwrap() = total;
+        }
+
+        let mut world = World::new();
+        world.spawn_many([1usize, 2]);
+        world.get_res::<usize>().get_or_init(|| 0);
+        world
+            .add_system(sync_double)
+            .add_async_system(async_sum)
+            .add_system(sync_double);
+
+        world.run_once();
+        assert_eq!(world.get_res::<usize>().get(), Some(&6));
+        world.run_once();
+        assert_eq!(world.get_res::<usize>().get(), Some(&24));
+
+        assert_eq!(
+            block_on(async {
+                YieldNow(false).await;
+                1
+            }),
+            1
+        );
+    }
+}
@@ -1,157 +1,458 @@
+#[cfg(feature = "async")]
+mod context;
+#[cfg(feature = "async")]
+mod executor;
+mod pipe;
 pub(crate) mod state;
 
-use crate::world::World;
-use state::SystemState;
-
-#[cfg(not(feature = "async"))]
-type Unit = ();
-
 #[cfg(feature = "async")]
 use std::{future::Future, pin::Pin};
+use std::{any::Any, error::Error};
+
+use crate::{
+    error::{SystemError, TrecsError},
+    world::World,
+};
+#[cfg(feature = "async")]
+pub use context::{AsyncContext, NextFrame};
 #[cfg(feature = "async")]
-type Unit = Pin<Box<dyn Future<Output = ()>>>;
+pub(crate) use context::{AsyncTask, Shared as AsyncShared};
+#[cfg(feature = "async")]
+pub use executor::block_on;
+pub use pipe::{In, Pipe};
+use state::SystemState;
 
 /// 函数系统 : 由实现了[FnSystemParm]特征的类型作为参数,并且加上
 /// [proc::system]属性的的函数
+///
+/// 也可以是只有一个`&mut World`参数的独占系统,可以对[World]做任何操作
+///
+/// [System]总是依次执行,[Commands]的操作也是立即生效的,
+/// 所以独占系统执行时不会有其他[System]在运行,也没有未完成的命令
+///
+/// 第一个参数是[In]的系统可以接收另一个系统的返回值,见[InnerSystem::pipe]
+///
+/// [Commands]: crate::world::Commands
 pub trait InnerSystem<Marker> {
-    /// 从[World]创建参数
-    fn build_args(&self, world: &World) -> Box<()>;
+    /// 通过[In]接收的输入,没有[In]参数时是`()`
+    type In;
+    /// 返回值
+    type Out;
+
+    /// 从[World]和[InnerSystem::init]返回的状态创建参数
+    ///
+    /// 独占系统直接借用整个[World],所以这里需要`&mut World`
+    fn build_args(&self, world: &mut World, state: &mut Box<dyn Any>) -> Box<()>;
+
+    /// 初始化,返回所有参数的状态
+    ///
+    /// 参数之间有冲突时返回[TrecsError]
+    fn init(&self) -> Result<Box<dyn Any>, TrecsError>;
+
+    fn run_once(&mut self, input: Self::In, args: Box<()>) -> Self::Out;
 
-    /// 初始化
-    fn init(&self);
+    /// 把返回值通过[In]传给system
+    ///
+    /// 两个系统先后执行,所以它们的参数之间不会冲突
+    ///
+    /// ```ignore
+    /// fn parse(res: Res<String>) -> usize {}
+    /// fn print(In(len): In<usize>) {}
+    ///
+    /// world.add_system(parse.pipe(print));
+    /// ```
+    fn pipe<M, S>(self, system: S) -> Pipe<Self, S>
+    where
+        Self: Sized,
+        S: InnerSystem<M, In = Self::Out>,
+    {
+        Pipe::new(self, system)
+    }
+}
+
+/// 可以作为[World::add_system]添加的系统的返回值
+///
+/// 返回的错误会交给[World::set_error_handler]设置的函数处理
+pub trait SystemOutput {
+    fn into_result(self) -> Result<(), Box<dyn Error>>;
+}
 
-    fn run_once(&mut self, args: Box<()>) -> Unit;
+impl SystemOutput for () {
+    fn into_result(self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+impl<E: Into<Box<dyn Error>>> SystemOutput for Result<(), E> {
+    fn into_result(self) -> Result<(), Box<dyn Error>> {
+        self.map_err(Into::into)
+    }
 }
 
 /// 实现此特征 就可以作为[System]的参数
 pub(crate) trait SystemParm {
-    /// 从[World]创建
+    /// 保存在[System]中,在多次运行之间保留的状态
+    type State: Default + 'static;
+
+    /// 从[World]和[SystemParm::State]创建
     ///
     /// # Safety
     ///
     /// 这个函数的安全性通过[FnSystemParm::init]保证
-    unsafe fn build(world: &World) -> Self;
+    unsafe fn build(world: &World, state: &mut Self::State) -> Self;
 
     /// 初始化,通过[SystemState]保证安全性
-    fn init(state: &mut SystemState);
+    ///
+    /// 和之前的参数冲突时返回[TrecsError]
+    fn init(state: &mut SystemState) -> Result<(), TrecsError>;
+}
+
+/// 可以缩短生命周期的[SystemParm]
+///
+/// [ParamSet]借出参数时使用,不要求参数中的类型是'static的
+///
+/// # Safety
+///
+/// Item<'w>必须是把Self中的生命周期换成'w之后的同一种类型,
+/// [ParamSet]会把创建出的Self直接当作Item<'w>使用
+///
+/// [ParamSet]: crate::world::ParamSet
+pub unsafe trait ReborrowParm {
+    /// 生命周期为'w的同一种参数
+    type Item<'w>
+    where
+        Self: 'w;
 }
 
 mod __impl {
 
-    #[cfg(not(feature = "async"))]
     mod _normal {
         use super::super::*;
         macro_rules! impl_fnsystem {
         ($($t:ident),*) => {
-            impl<F,$($t : SystemParm,)*> InnerSystem<($($t,)*)> for F
-            where F : FnMut($($t,)*) {
-                fn build_args(&self, world: &World) -> Box<()>{
+            impl<F,O,$($t : SystemParm,)*> InnerSystem<($($t,)*)> for F
+            where F : FnMut($($t,)*) -> O {
+                type In = ();
+                type Out = O;
+
+                fn build_args(&self, world: &mut World, state: &mut Box<dyn Any>) -> Box<()>{
+                    let ($($t,)*) = state.downcast_mut::<($($t::State,)*)>().unwrap();
                     unsafe{
-                        std::mem::transmute(Box::new(($($t::build(world),)*)))
+                        std::mem::transmute(Box::new(($($t::build(world, $t),)*)))
                     }
                 }
 
-                fn init(&self) {
+                fn init(&self) -> Result<Box<dyn Any>, TrecsError> {
                     let mut state = SystemState::new();
-                    $($t::init(&mut state);)*
+                    $($t::init(&mut state)?;)*
+                    Ok(Box::new(($($t::State::default(),)*)))
                 }
 
-                fn run_once(&mut self, args: Box<()>) -> Unit{
+                fn run_once(&mut self, _input: (), args: Box<()>) -> O{
                     let ($($t,)*) = unsafe{
                         *std::mem::transmute::<_,Box<($($t,)*)>>(args)
                     };
                     (self)($($t,)*)
                 }
             }
+
+            impl<F,I,O,$($t : SystemParm,)*> InnerSystem<(In<I>,$($t,)*)> for F
+            where F : FnMut(In<I>,$($t,)*) -> O {
+                type In = I;
+                type Out = O;
+
+                fn build_args(&self, world: &mut World, state: &mut Box<dyn Any>) -> Box<()>{
+                    let ($($t,)*) = state.downcast_mut::<($($t::State,)*)>().unwrap();
+                    unsafe{
+                        std::mem::transmute(Box::new(($($t::build(world, $t),)*)))
+                    }
+                }
+
+                fn init(&self) -> Result<Box<dyn Any>, TrecsError> {
+                    let mut state = SystemState::new();
+                    $($t::init(&mut state)?;)*
+                    Ok(Box::new(($($t::State::default(),)*)))
+                }
+
+                fn run_once(&mut self, input: I, args: Box<()>) -> O{
+                    let ($($t,)*) = unsafe{
+                        *std::mem::transmute::<Box<()>,Box<($($t,)*)>>(args)
+                    };
+                    (self)(In(input),$($t,)*)
+                }
+            }
             };
         }
         trecs_proc::all_tuple!(impl_fnsystem, 16);
-        impl<F> InnerSystem<()> for F
+        impl<F, O> InnerSystem<()> for F
         where
-            F: FnMut(),
+            F: FnMut() -> O,
         {
-            fn build_args(&self, _world: &World) -> Box<()> {
+            type In = ();
+            type Out = O;
+
+            fn build_args(&self, _world: &mut World, _state: &mut Box<dyn Any>) -> Box<()> {
                 Box::new(())
             }
 
-            fn init(&self) {}
+            fn init(&self) -> Result<Box<dyn Any>, TrecsError> {
+                Ok(Box::new(()))
+            }
 
-            fn run_once(&mut self, _args: Box<()>) -> Unit {
+            fn run_once(&mut self, _input: (), _args: Box<()>) -> O {
                 (self)()
             }
         }
-    }
 
-    #[cfg(feature = "async")]
-    mod _async {
-        use super::super::*;
-        macro_rules! impl_async_fnsystem {
-        ($($t:ident),*) => {
-            impl<F,R,$($t : SystemParm,)*> InnerSystem<($($t,)*)> for F
-            where F : FnMut($($t,)*) -> R,
-                  R: Future<Output = ()> + 'static,
-            {
-                fn build_args(&self, world: &World) -> Box<()>{
-                    unsafe{
-                        std::mem::transmute(Box::new(($($t::build(world),)*)))
-                    }
-                }
+        impl<F, I, O> InnerSystem<(In<I>,)> for F
+        where
+            F: FnMut(In<I>) -> O,
+        {
+            type In = I;
+            type Out = O;
 
-                fn init(&self) {
-                    let mut state = SystemState::new();
-                    $($t::init(&mut state);)*
-                }
+            fn build_args(&self, _world: &mut World, _state: &mut Box<dyn Any>) -> Box<()> {
+                Box::new(())
+            }
 
-                fn run_once(&mut self, args: Box<()>) -> Unit{
-                    let ($($t,)*) = unsafe{
-                        *std::mem::transmute::<_,Box<($($t,)*)>>(args)
-                    };
-                    Box::pin((self)($($t,)*))
-                }
+            fn init(&self) -> Result<Box<dyn Any>, TrecsError> {
+                Ok(Box::new(()))
+            }
+
+            fn run_once(&mut self, input: I, _args: Box<()>) -> O {
+                (self)(In(input))
             }
-            };
         }
-        trecs_proc::all_tuple!(impl_async_fnsystem, 16);
-        #[cfg(feature = "async")]
-        impl<F, R> InnerSystem<()> for F
+
+        impl<F, O> InnerSystem<fn(&mut World)> for F
         where
-            F: FnMut() -> R,
-            R: Future<Output = ()> + 'static,
+            F: FnMut(&mut World) -> O,
         {
-            fn build_args(&self, _world: &World) -> Box<()> {
-                Box::new(())
+            type In = ();
+            type Out = O;
+
+            fn build_args(&self, world: &mut World, _state: &mut Box<dyn Any>) -> Box<()> {
+                let world: *mut World = world;
+                unsafe { std::mem::transmute(Box::new(world)) }
             }
 
-            fn init(&self) {}
+            fn init(&self) -> Result<Box<dyn Any>, TrecsError> {
+                Ok(Box::new(()))
+            }
 
-            fn run_once(&mut self, _args: Box<()>) -> Unit {
-                Box::pin((self)())
+            fn run_once(&mut self, _input: (), args: Box<()>) -> O {
+                // 指针来自build_args借到的&mut World,期间没有其他借用
+                let world = unsafe { *std::mem::transmute::<Box<()>, Box<*mut World>>(args) };
+                (self)(unsafe { &mut *world })
             }
         }
     }
 }
 
+/// [SystemOutput]转换后的结果
+type SystemResult = Result<(), Box<dyn Error>>;
+
+/// [System]执行一次的结果
+pub(crate) enum Outcome {
+    Done(SystemResult),
+    /// 异步系统返回的[Future],由[block_on]执行
+    #[cfg(feature = "async")]
+    Pending(Pin<Box<dyn Future<Output = SystemResult>>>),
+}
+
+/// 返回值已经转换成[Outcome]的[InnerSystem]
+type ErasedSystem = dyn InnerSystem<(), In = (), Out = Outcome>;
+
 #[non_exhaustive]
 pub struct System {
-    inner: Box<dyn InnerSystem<()>>,
+    inner: Box<ErasedSystem>,
+    /// 参数的状态,比如[QueryState]
+    ///
+    /// [QueryState]: crate::world::QueryState
+    state: Box<dyn Any>,
+    /// 系统的类型名,用于[SystemError]
+    name: &'static str,
 }
 
 impl System {
-    pub(crate) fn new<M, F: InnerSystem<M>>(fn_system: F) -> Self {
-        fn_system.init();
-        let fn_system: Box<dyn InnerSystem<M>> = Box::new(fn_system);
+    /// 参数之间有冲突时返回[TrecsError]
+    pub(crate) fn new<M, F>(fn_system: F) -> Result<Self, TrecsError>
+    where
+        F: InnerSystem<M, In = ()> + 'static,
+        F::Out: SystemOutput + 'static,
+    {
+        let name = std::any::type_name::<F>();
+        let fn_system = fn_system.pipe(|In(out): In<F::Out>| Outcome::Done(out.into_result()));
+        Self::erase(fn_system, name)
+    }
+
+    /// 返回[Future]的异步系统
+    ///
+    /// 参数之间有冲突时返回[TrecsError]
+    #[cfg(feature = "async")]
+    pub(crate) fn new_async<M, F>(fn_system: F) -> Result<Self, TrecsError>
+    where
+        F: InnerSystem<M, In = ()> + 'static,
+        F::Out: Future + 'static,
+        <F::Out as Future>::Output: SystemOutput,
+    {
+        let name = std::any::type_name::<F>();
+        let fn_system = fn_system.pipe(|In(future): In<F::Out>| {
+            Outcome::Pending(Box::pin(async move { future.await.into_result() }))
+        });
+        Self::erase(fn_system, name)
+    }
+
+    fn erase<M, F>(fn_system: F, name: &'static str) -> Result<Self, TrecsError>
+    where
+        F: InnerSystem<M, In = (), Out = Outcome> + 'static,
+    {
+        let state = fn_system.init()?;
+        let fn_system: Box<dyn InnerSystem<M, In = (), Out = Outcome>> = Box::new(fn_system);
 
-        let inner: Box<dyn InnerSystem<()>> = unsafe { std::mem::transmute(fn_system) };
+        let inner: Box<ErasedSystem> = unsafe { std::mem::transmute(fn_system) };
 
-        Self { inner }
+        Ok(Self { inner, state, name })
     }
 
-    #[cfg(not(feature = "async"))]
-    pub(crate) fn run_once(&mut self, world: &World) {
-        self.inner.run_once(self.inner.build_args(world));
+    /// 执行一次,异步系统会阻塞直到完成
+    pub(crate) fn run_once(&mut self, world: &mut World) -> Result<(), SystemError> {
+        // 没有async特性时只有Outcome::Done
+        #[allow(clippy::infallible_destructuring_match)]
+        let result = match self
+            .inner
+            .run_once((), self.inner.build_args(world, &mut self.state))
+        {
+            Outcome::Done(result) => result,
+            #[cfg(feature = "async")]
+            Outcome::Pending(future) => block_on(future),
+        };
+        result.map_err(|error| SystemError::new(self.name, error))
     }
-    #[cfg(feature = "async")]
-    pub(crate) async fn run_once(&mut self, world: &World) {
-        self.inner.run_once(self.inner.build_args(world)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{In, InnerSystem};
+    use crate::{
+        error::{handler, SystemError},
+        storage::Entity,
+        tools::{Command, ResManager},
+        world::{Query, Res},
+        World,
+    };
+
+    fn sum(query: Query<&usize>) -> usize {
+        query.into_iter().sum()
+    }
+
+    fn check(In(sum): In<usize>, limit: Res<usize>) -> Result<(), String> {
+        match limit.get() {
+            Some(limit) if sum > *limit => Err(format!("{sum} > {limit}")),
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn exec_and_pipe() {
+        let mut world = World::new();
+        world.spawn_many([1usize, 2, 3]);
+        assert_eq!(world.exec(sum), 6);
+
+        let entity = world.exec(|world: &mut World| world.spawn(4usize));
+        assert_eq!(world.get::<usize>(entity), Some(&4));
+        assert_eq!(world.exec(sum.pipe(|In(sum): In<usize>| sum * 2)), 20);
+    }
+
+    #[test]
+    fn exclusive_then_query() {
+        let mut world = World::new();
+        world.spawn(1usize);
+        let spawn = |world: &mut World| world.spawn(2usize);
+        // 第二个系统的参数在独占系统执行完之后才创建
+        let total = world.exec(spawn.pipe(|In(entity): In<Entity>, query: Query<&usize>| {
+            assert_eq!(query.get(entity), Some(&2));
+            query.into_iter().sum::<usize>()
+        }));
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    fn error_handler() {
+        let mut world = World::new();
+        world.spawn_many([1usize, 2]);
+        world.get_res::<usize>().get_or_init(|| 5);
+        world
+            .set_error_handler(handler::collect)
+            .add_system(sum.pipe(check));
+
+        world.run_once();
+        assert!(world.get_res::<Vec<SystemError>>().get().is_none());
+
+        world.spawn(3usize);
+        world.run_once();
+        world.run_once();
+        let errors = world.get_res::<Vec<SystemError>>();
+        let errors = errors.get().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].error.to_string(), "6 > 5");
+        assert!(errors[0].to_string().starts_with("system `"));
+    }
+
+    #[test]
+    #[should_panic(expected = "6 > 5")]
+    fn panic_by_default() {
+        let mut world = World::new();
+        world.spawn_many([1usize, 2, 3]);
+        world.get_res::<usize>().get_or_init(|| 5);
+        world.add_system(sum.pipe(check));
+        world.run_once();
+    }
+
+    #[test]
+    fn add_system_from_exclusive() {
+        fn count(mut counter: Res<usize>) {
+            *counter.get_mut().unwrap() += 1;
+        }
+
+        fn spawner(world: &mut World) {
+            world.add_system(count);
+        }
+
+        let mut world = World::new();
+        world.get_res::<usize>().get_or_init(|| 0);
+        world.add_system(spawner);
+        world.run_once();
+        assert_eq!(world.get_res::<usize>().get(), Some(&0));
+        world.run_once();
+        assert_eq!(world.get_res::<usize>().get(), Some(&1));
+        assert_eq!(world.systems.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "inside a running system")]
+    fn reentrant_run_once() {
+        let mut world = World::new();
+        world.add_system(|world: &mut World| world.run_once());
+        world.run_once();
+    }
+
+    #[test]
+    fn shutdown_every_run() {
+        fn bye(mut counter: Res<usize>) {
+            *counter.get_mut().unwrap() += 1;
+        }
+
+        let mut world = World::new();
+        world.get_res::<usize>().get_or_init(|| 0);
+        world
+            .add_system(|world: &mut World| {
+                world.exit();
+            })
+            .add_shutdown_system(bye);
+        world.run();
+        assert!(!world.exit_requested());
+        world.run();
+        assert_eq!(world.get_res::<usize>().get(), Some(&2));
     }
 }
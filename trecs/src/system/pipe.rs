@@ -0,0 +1,68 @@
+use std::any::Any;
+
+use super::InnerSystem;
+use crate::{error::TrecsError, world::World};
+
+/// 系统的第一个参数,接收前一个系统的返回值
+///
+/// ```ignore
+/// fn print(In(len): In<usize>, query: Query<&Name>) {}
+/// ```
+///
+/// [InnerSystem::pipe]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct In<T>(pub T);
+
+/// 先后执行两个系统,把第一个系统的返回值交给第二个系统
+///
+/// 由[InnerSystem::pipe]创建
+pub struct Pipe<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Pipe<A, B> {
+    pub(crate) fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+}
+
+/// 两个系统的状态
+type States = (Box<dyn Any>, Box<dyn Any>);
+
+/// [Pipe]的[InnerSystem::build_args]的返回值
+///
+/// 第二个系统的参数要在第一个系统执行完之后再创建,所以只保存[World]和状态
+type Args = (*mut World, *mut States);
+
+impl<A, B, MA, MB> InnerSystem<(MA, MB)> for Pipe<A, B>
+where
+    A: InnerSystem<MA>,
+    B: InnerSystem<MB, In = A::Out>,
+{
+    type In = A::In;
+    type Out = B::Out;
+
+    fn build_args(&self, world: &mut World, state: &mut Box<dyn Any>) -> Box<()> {
+        let args: Args = (world, state.downcast_mut::<States>().unwrap());
+        unsafe { std::mem::transmute::<Box<Args>, Box<()>>(Box::new(args)) }
+    }
+
+    fn init(&self) -> Result<Box<dyn Any>, TrecsError> {
+        let states: States = (self.first.init()?, self.second.init()?);
+        Ok(Box::new(states))
+    }
+
+    fn run_once(&mut self, input: A::In, args: Box<()>) -> B::Out {
+        // World和状态都保存在调用者中,比这次执行活得更久
+        let (world, (first, second)) = unsafe {
+            let (world, states) = *std::mem::transmute::<Box<()>, Box<Args>>(args);
+            (&mut *world, &mut *states)
+        };
+        let out = self
+            .first
+            .run_once(input, self.first.build_args(world, first));
+        self.second
+            .run_once(out, self.second.build_args(world, second))
+    }
+}
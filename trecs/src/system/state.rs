@@ -3,7 +3,7 @@ use std::{
     collections::{HashMap, HashSet},
 };
 
-use crate::tools::WorldFetch;
+use crate::{error::TrecsError, tools::WorldFetch};
 
 /// [System]的"状态"
 ///
@@ -13,6 +13,7 @@ use crate::tools::WorldFetch;
 /// 算是最后抢救一下unsafe遍布的代码吧
 ///
 /// 仅仅在[System]第一次执行时进行计算,
+#[derive(Clone)]
 pub struct SystemState {
     pub(crate) alias_map: AliasMap,
     pub(crate) resources: bool,
@@ -29,6 +30,19 @@ impl SystemState {
     }
 }
 
+impl SystemState {
+    /// 合并另一个[SystemState]中的使用情况
+    ///
+    /// 不会检查两者之间的冲突,用于[ParamSet]中不会同时存在的参数
+    ///
+    /// [ParamSet]: crate::world::ParamSet
+    pub(crate) fn merge(&mut self, other: SystemState) {
+        self.alias_map.merge(other.alias_map);
+        self.resources |= other.resources;
+        self.res.extend(other.res);
+    }
+}
+
 impl Default for SystemState {
     fn default() -> Self {
         Self::new()
@@ -38,6 +52,7 @@ impl Default for SystemState {
 /// 用来检测别名冲突
 ///
 /// 枚举引用的使用情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Alias {
     /// 有不可变引用
     Imut,
@@ -63,37 +78,119 @@ impl Alias {
     }
 }
 
+/// 一个[Query]一定存在和一定不存在的[Component]
+///
+/// 由[WorldFilter::archetype_filter]和[WorldFetch]使用的[Component]得到
+///
+/// [Query]: crate::world::Query
+/// [Component]: crate
+/// [WorldFilter::archetype_filter]: crate::tools::WorldFilter::archetype_filter
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchetypeFilter {
+    with: HashSet<TypeId>,
+    without: HashSet<TypeId>,
+}
+
+impl ArchetypeFilter {
+    pub fn with(&mut self, id: TypeId) {
+        self.with.insert(id);
+    }
+
+    pub fn without(&mut self, id: TypeId) {
+        self.without.insert(id);
+    }
+
+    /// 两者不可能匹配同一个[BundleMeta]
+    ///
+    /// 即一方要求存在的[Component]被另一方要求不存在
+    ///
+    /// [BundleMeta]: crate
+    /// [Component]: crate
+    pub fn is_disjoint(&self, other: &ArchetypeFilter) -> bool {
+        !self.with.is_disjoint(&other.without) || !self.without.is_disjoint(&other.with)
+    }
+}
+
+/// 对一种类型的一次使用
+#[derive(Clone, PartialEq, Eq)]
+struct Usage {
+    alias: Alias,
+    /// 使用类型的Fetch
+    user: &'static str,
+    filter: ArchetypeFilter,
+}
+
 /// 计算别名冲突
+///
+/// 两个[Query]的[ArchetypeFilter]不相交时,即使使用了同一种类型的可变引用也不会冲突
+///
+/// [Query]: crate::world::Query
+#[derive(Clone)]
 pub struct AliasMap {
-    /// <类型的ID,(别名情况,使用类型的Fetch)
-    inner: HashMap<TypeId, (Alias, Vec<&'static str>)>,
+    /// 类型的ID,以及所有的使用情况
+    inner: HashMap<TypeId, Vec<Usage>>,
+    /// 正在计算的[WorldFetch]使用的类型,在[AliasMap::commit]时检查
+    pending: Vec<(TypeId, &'static str, Alias, &'static str)>,
 }
 
 impl AliasMap {
     pub fn new() -> Self {
         Self {
             inner: Default::default(),
+            pending: vec![],
         }
     }
 
+    /// 记录[WorldFetch]F对T的使用
+    ///
+    /// 在[AliasMap::commit]之前不会检查冲突
     pub fn insert<F: WorldFetch, T: Any>(&mut self, usage: Alias) {
-        let (ty, ty_name) = (TypeId::of::<T>(), type_name::<T>());
-        if let std::collections::hash_map::Entry::Vacant(e) = self.inner.entry(ty) {
-            e.insert((usage, vec![type_name::<F>()]));
-        } else {
-            let (alias, users) = self.inner.get_mut(&ty).unwrap();
-            if usage.is_mut() && alias.is_imut() {
-                let users = users
-                    .iter()
-                    .fold(String::new(), |str, user| str + user + " ");
-                panic!("发生别名冲突: WorldFetch [{}] 都使用了{}的不可变引用,而 WorldFetch {}使用了{}的可变引用\n",
-                    users,ty_name,type_name::<F>(),ty_name);
+        self.pending
+            .push((TypeId::of::<T>(), type_name::<T>(), usage, type_name::<F>()));
+    }
+
+    /// 检查insert记录的使用是否和之前的使用冲突
+    ///
+    /// filter是[WorldFilter]的要求,[WorldFetch]使用的类型也一定存在
+    ///
+    /// [WorldFilter]: crate::tools::WorldFilter
+    pub fn commit(&mut self, mut filter: ArchetypeFilter) -> Result<(), TrecsError> {
+        let pending = std::mem::take(&mut self.pending);
+        for (ty, ..) in &pending {
+            filter.with(*ty);
+        }
+        for (ty, ty_name, alias, user) in pending {
+            let usages = self.inner.entry(ty).or_default();
+            let conflict = usages.iter().find(|usage| {
+                (alias.is_mut() || usage.alias.is_mut()) && !usage.filter.is_disjoint(&filter)
+            });
+            if let Some(usage) = conflict {
+                return Err(TrecsError::AliasConflict {
+                    component: ty_name,
+                    first: usage.user,
+                    first_mut: usage.alias.is_mut(),
+                    second: user,
+                    second_mut: alias.is_mut(),
+                });
             }
-            if usage.is_imut() && alias.is_mut() {
-                panic!("发生别名冲突: WorldFetch [{}] 使用了{}的不可变引用,而 WorldFetch {}使用了{}的可变引用\n",
-                    type_name::<F>(),ty_name,users[0],ty_name);
+            usages.push(Usage {
+                alias,
+                user,
+                filter: filter.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// 合并另一个[AliasMap]中的使用情况
+    fn merge(&mut self, other: AliasMap) {
+        for (ty, usages) in other.inner {
+            let old = self.inner.entry(ty).or_default();
+            for usage in usages {
+                if !old.contains(&usage) {
+                    old.push(usage);
+                }
             }
-            users.push(type_name::<F>());
         }
     }
 }
@@ -103,3 +200,88 @@ impl Default for AliasMap {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::TrecsError,
+        tools::{Command, With, Without},
+        world::{Query, Res, Resources},
+        World,
+    };
+
+    #[test]
+    fn disjoint() {
+        fn filtered(a: Query<&mut usize, With<u8>>, b: Query<&mut usize, Without<u8>>) {
+            assert_eq!(a.len(), 1);
+            assert_eq!(b.len(), 2);
+        }
+        // 使用的类型也一定存在
+        fn fetched(_a: Query<&mut usize, Without<u8>>, _b: Query<(&mut usize, &u8)>) {}
+
+        let mut world = World::new();
+        world.spawn((1usize, 1u8));
+        world.spawn(2usize);
+        world.spawn((3usize, true));
+        world.exec(filtered);
+        world.exec(fetched);
+    }
+
+    #[test]
+    fn conflict() {
+        fn conflict(_a: Query<&mut usize, With<u8>>, _b: Query<&usize, With<bool>>) {}
+        let error = World::new().try_add_system(conflict).err().unwrap();
+        assert_eq!(
+            error,
+            TrecsError::AliasConflict {
+                component: "usize",
+                first: "&mut usize",
+                first_mut: true,
+                second: "&usize",
+                second_mut: false,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "alias conflict on component `usize`: fetch `&mut usize` takes a mutable reference \
+             while fetch `&usize` takes a immutable reference"
+        );
+    }
+
+    #[test]
+    fn resources() {
+        fn duplicate(_a: Res<usize>, _b: Res<usize>) {}
+        fn mixed(_a: Res<usize>, _b: Resources) {}
+        fn mixed_rev(_a: Resources, _b: Res<usize>) {}
+
+        let mut world = World::new();
+        assert_eq!(
+            world.try_add_system(duplicate).err(),
+            Some(TrecsError::DuplicateRes { resource: "usize" })
+        );
+        assert_eq!(
+            world.try_add_startup_system(mixed).err(),
+            Some(TrecsError::ResourcesConflict { param: "Res" })
+        );
+        assert_eq!(
+            world.try_add_system(mixed_rev).err(),
+            Some(TrecsError::ResourcesConflict { param: "Res" })
+        );
+        assert!(world.systems.is_empty() && world.startup_systems.is_empty());
+    }
+
+    #[test]
+    fn both_mut() {
+        fn both_mut(_a: Query<&mut usize>, _b: Query<&mut usize, Without<u8>>) {}
+        assert_eq!(
+            World::new().try_add_system(both_mut).err(),
+            Some(TrecsError::AliasConflict {
+                component: "usize",
+                first: "&mut usize",
+                first_mut: true,
+                second: "&mut usize",
+                second_mut: true,
+            })
+        );
+    }
+}
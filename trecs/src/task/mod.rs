@@ -0,0 +1,367 @@
+use std::{
+    any::{Any, TypeId},
+    error::Error,
+    fmt::{Debug, Display},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::bundle::Component;
+
+#[cfg(feature = "system")]
+use crate::{iter::EIter, world::Commands, world::Query};
+
+/// 交给工作线程执行的闭包
+type Job = Box<dyn FnOnce() + Send>;
+
+/// 在后台线程中执行闭包的线程池
+///
+/// 通常作为资源储存在[World]中,在[System]中通过`Res<TaskPool>`使用
+///
+/// ```ignore
+/// world.get_res::<TaskPool>().get_or_init(TaskPool::default);
+///
+/// fn load(pool: Res<TaskPool>, mut commands: Commands) {
+///     let task = pool.get().unwrap().spawn(|| Level::load("level.json"));
+///     commands.spawn(task);
+/// }
+///
+/// world.add_system(apply_tasks::<Level>);
+/// ```
+///
+/// [World]: crate::World
+/// [System]: crate::system::System
+pub struct TaskPool {
+    sender: Sender<Job>,
+    threads: usize,
+}
+
+impl TaskPool {
+    /// 创建有threads个工作线程的线程池
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0, "a task pool needs at least one thread");
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads {
+            let receiver = receiver.clone();
+            // 线程池被Drop后发送端关闭,工作线程在执行完剩下的任务后退出
+            thread::spawn(move || loop {
+                let job = match receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => return,
+                };
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => return,
+                }
+            });
+        }
+        Self { sender, threads }
+    }
+
+    /// 工作线程的数量
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// 在工作线程中执行f,返回可以获取结果的[Task]
+    pub fn spawn<T, F>(&self, f: F) -> Task<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let job = move || {
+            // 发生panic时交给Task::poll处理,工作线程继续执行之后的任务
+            let result = catch_unwind(AssertUnwindSafe(f));
+            let _ = sender.send(result);
+        };
+        self.sender
+            .send(Box::new(job))
+            .expect("all worker threads of the task pool have exited");
+        Task {
+            receiver,
+            finished: false,
+        }
+    }
+
+    /// 在工作线程中执行future,直到完成
+    #[cfg(feature = "async")]
+    pub fn spawn_future<F>(&self, future: F) -> Task<F::Output>
+    where
+        F: std::future::Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn(move || crate::system::block_on(future))
+    }
+}
+
+/// 线程数和CPU的核数相同
+impl Default for TaskPool {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+impl Debug for TaskPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskPool")
+            .field("threads", &self.threads)
+            .finish()
+    }
+}
+
+/// [TaskPool::spawn]返回的任务句柄
+///
+/// 可以作为[Component]储存在[Entity]上,由[apply_tasks]在完成后替换成结果
+///
+/// [Entity]: crate::storage::Entity
+pub struct Task<T> {
+    receiver: Receiver<thread::Result<T>>,
+    finished: bool,
+}
+
+impl<T> Task<T> {
+    /// 任务完成时返回结果,之后再调用会返回[None]
+    ///
+    /// 任务中发生的panic会在这里继续,使用[Task::try_poll]获得[TaskPanic]
+    pub fn poll(&mut self) -> Option<T> {
+        self.try_poll()
+            .map(|result| result.unwrap_or_else(|panic| resume_unwind(panic.payload)))
+    }
+
+    /// 任务完成时返回结果,之后再调用会返回[None]
+    ///
+    /// 任务中发生panic时返回[TaskPanic]
+    pub fn try_poll(&mut self) -> Option<Result<T, TaskPanic>> {
+        if self.finished {
+            return None;
+        }
+        match self.receiver.try_recv() {
+            Ok(result) => {
+                self.finished = true;
+                Some(result.map_err(|payload| TaskPanic { payload }))
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+
+    /// 结果是否已经被[Task::poll]取走
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// 阻塞直到任务完成,返回结果
+    ///
+    /// 结果已经被取走时返回[None]
+    pub fn block(mut self) -> Option<T> {
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        let result = self.receiver.recv().ok()?;
+        Some(result.unwrap_or_else(|panic| resume_unwind(panic)))
+    }
+}
+
+/// 任务中发生的panic
+///
+/// 由[Task::try_poll]和[apply_tasks]返回
+pub struct TaskPanic {
+    payload: Box<dyn Any + Send>,
+}
+
+impl TaskPanic {
+    /// panic的信息,不是字符串时返回[None]
+    pub fn message(&self) -> Option<&str> {
+        self.payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| self.payload.downcast_ref::<String>().map(String::as_str))
+    }
+
+    /// 取出panic的值,可以交给[resume_unwind]继续panic
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl Debug for TaskPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskPanic")
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+impl Display for TaskPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "task panicked: {message}"),
+            None => write!(f, "task panicked"),
+        }
+    }
+}
+
+impl Error for TaskPanic {}
+
+impl<T: Any> Component for Task<T> {
+    fn type_id_() -> TypeId {
+        TypeId::of::<Self>()
+    }
+}
+
+impl<T> Debug for Task<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Task")
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+/// 把已经完成的[Task]替换成它的结果
+///
+/// 发生panic的[Task]会被移除,并且返回[TaskPanic]交给[World]的错误处理函数,
+/// 同时有多个时只返回第一个
+///
+/// ```ignore
+/// world.add_system(apply_tasks::<Level>);
+/// ```
+///
+/// [World]: crate::World
+#[cfg(feature = "system")]
+pub fn apply_tasks<T: Component>(
+    mut query: Query<&mut Task<T>>,
+    mut commands: Commands,
+) -> Result<(), TaskPanic> {
+    let finished = EIter::from(query.iter_mut())
+        .filter_map(|mut task| Some((task.entity(), task.try_poll()?)))
+        .collect::<Vec<_>>();
+    let mut panic = None;
+    for (entity, result) in finished {
+        let Some(mut entity) = commands.entity_mut(entity) else {
+            continue;
+        };
+        entity.remove::<Task<T>>();
+        match result {
+            Ok(value) => {
+                entity.insert(value);
+            }
+            Err(error) => {
+                panic.get_or_insert(error);
+            }
+        }
+    }
+    panic.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::TypeId, time::Duration};
+
+    use super::TaskPool;
+    use crate::bundle::Component;
+
+    #[derive(Debug, PartialEq)]
+    struct Path(Vec<u32>);
+
+    impl Component for Path {
+        fn type_id_() -> TypeId {
+            TypeId::of::<Self>()
+        }
+    }
+
+    #[test]
+    fn task_pool() {
+        let pool = TaskPool::new(2);
+        let tasks = (0..4u32)
+            .map(|i| pool.spawn(move || i * 2))
+            .collect::<Vec<_>>();
+        let results = tasks
+            .into_iter()
+            .map(|task| task.block().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(results, vec![0, 2, 4, 6]);
+
+        let mut task = pool.spawn(|| "done");
+        while task.poll().is_none() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(task.is_finished());
+        assert_eq!(task.poll(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn task_panic() {
+        let pool = TaskPool::new(1);
+        pool.spawn(|| panic!("boom")).block();
+    }
+
+    #[cfg(feature = "system")]
+    #[test]
+    fn apply_tasks() {
+        use super::{apply_tasks, Task};
+        use crate::{
+            tools::{Command, ResManager},
+            world::{Query, Res},
+            World,
+        };
+
+        fn find_path(pool: Res<TaskPool>, mut commands: crate::world::Commands) {
+            let task = pool.get().unwrap().spawn(|| Path(vec![1, 2, 3]));
+            commands.spawn((task, 1u8));
+        }
+
+        let mut world = World::new();
+        world.get_res::<TaskPool>().get_or_init(|| TaskPool::new(1));
+        world.add_startup_system(find_path);
+        world.add_system(apply_tasks::<Path>);
+        world.startup();
+
+        while Query::<&Task<Path>>::new(&mut world).into_iter().count() == 1 {
+            std::thread::sleep(Duration::from_millis(1));
+            world.run_once();
+        }
+        assert_eq!(
+            Query::<(&Path, &u8)>::new(&mut world).single(),
+            Ok((&Path(vec![1, 2, 3]), &1))
+        );
+    }
+
+    #[cfg(feature = "system")]
+    #[test]
+    fn apply_tasks_panic() {
+        use super::{apply_tasks, Task};
+        use crate::{
+            error::{handler, SystemError},
+            tools::{Command, ResManager},
+            world::Query,
+            World,
+        };
+
+        let mut world = World::new();
+        let pool = TaskPool::new(1);
+        world.spawn(pool.spawn(|| -> Path { panic!("boom") }));
+        world
+            .set_error_handler(handler::collect)
+            .add_system(apply_tasks::<Path>);
+
+        while Query::<&Task<Path>>::new(&mut world).into_iter().count() == 1 {
+            std::thread::sleep(Duration::from_millis(1));
+            world.run_once();
+        }
+        let errors = world.get_res::<Vec<SystemError>>();
+        let errors = errors.get().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error.to_string(), "task panicked: boom");
+    }
+}
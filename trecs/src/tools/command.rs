@@ -1,6 +1,7 @@
 #[allow(unused)]
 use crate::{
-    bundle::{Bundle, BundleMeta},
+    bundle::{Bundle, BundleMeta, Relation},
+    hierarchy::{Children, Parent},
     storage::Entity,
     World,
 };
@@ -30,8 +31,43 @@ pub trait Command {
     fn alive(&self, entity: Entity) -> Option<bool>;
     /// 从[World]中删除[Entity]代表的[Bundle]
     ///
+    /// 如果[Entity]处于层级关系中,会从父节点的[Children]中移除,
+    /// 它的子节点会失去[Parent],成为根节点
+    ///
+    /// 所有和[Entity]有关的[Relation]都会被删除
+    ///
     /// 返回[Entity]代表的[Bundle]是否存在
     fn remove(&mut self, entity: Entity) -> bool;
     /// 在[Entity]对应的[Bundle]上进行[WorldFetch]
     fn fetch<F: WorldFetch>(&mut self, entity: Entity) -> Option<F::Item<'_>>;
+    /// 将child的父节点设为parent
+    ///
+    /// 会同时维护child上的[Parent]和parent上的[Children]
+    ///
+    /// + 任意一个[Entity]失效,或者parent是child自身或其后代时返回false,什么都不做
+    /// + 如果child原本有父节点,会先从原父节点的[Children]中移除
+    fn set_parent(&mut self, child: Entity, parent: Entity) -> bool;
+    /// 移除child的父节点
+    ///
+    /// 返回原来的父节点,如果没有父节点返回[None]
+    fn remove_parent(&mut self, child: Entity) -> Option<Entity>;
+    /// 从[World]中删除[Entity]以及它的所有后代
+    ///
+    /// 返回[Entity]是否存在
+    fn despawn_recursive(&mut self, entity: Entity) -> bool;
+    /// 建立一对source到target的[Relation]
+    ///
+    /// + 任意一个[Entity]失效时返回false,什么都不做
+    /// + 重复的关系只会记录一次
+    fn relate<R: Relation>(&mut self, source: Entity, target: Entity) -> bool;
+    /// 删除一对source到target的[Relation]
+    ///
+    /// 返回关系是否存在
+    fn unrelate<R: Relation>(&mut self, source: Entity, target: Entity) -> bool;
+    /// source通过[Relation]指向的所有[Entity]
+    fn targets<R: Relation>(&self, source: Entity) -> &[Entity];
+    /// 通过[Relation]指向target的所有[Entity]
+    fn sources<R: Relation>(&self, target: Entity) -> &[Entity];
+    /// 所有的(source,target)关系
+    fn pairs<R: Relation>(&self) -> Vec<(Entity, Entity)>;
 }
@@ -81,6 +81,11 @@ pub trait WorldFetch {
     /// + 返回[None]代表无法转换
     fn contain(components_ids: &mut Vec<TypeId>) -> Option<MappingTable>;
 
+    /// 把[WorldFetch]可写的[Component]的[TypeId]放入ids
+    ///
+    /// 用来追踪[Component]可能发生的变化
+    fn writes(ids: &mut Vec<TypeId>);
+
     /// 计算[WorldFetch]可能导致的别名冲突
     ///
     /// 如果存在别名冲突,带有发生冲突的[WorldFetch]在第一次执行时会发生painc
@@ -88,6 +93,33 @@ pub trait WorldFetch {
     fn alias_conflict(alias_map: &mut AliasMap);
 }
 
+/// 只读的[WorldFetch]
+///
+/// 只有只读的[Query]可以通过共享引用遍历,或者同时存在多个迭代器
+///
+/// # Safety
+///
+/// [WorldFetch::Item]中不能有任何可变引用
+///
+/// [Query]: crate
+pub unsafe trait ReadOnlyFetch: WorldFetch {}
+
+/// 被[WorldFetch::contain]取走的[Component]会被替换成这个类型的[TypeId]
+///
+/// 不能直接删除,否则后面的下标都会错位
+struct Taken;
+
+/// 从components_ids中找到T,并将其标记为已经被取走
+///
+/// 同一个[Component]不能在同一个[WorldFetch]中出现两次
+fn take_component<T: Component>(components_ids: &mut [TypeId]) -> Option<MappingTable> {
+    let mapping = components_ids
+        .iter()
+        .position(|id| *id == TypeId::of::<T>())?;
+    components_ids[mapping] = TypeId::of::<Taken>();
+    Some(MappingTable::Mapping(mapping))
+}
+
 impl<T: Component> WorldFetch for &T {
     type Item<'a> = &'a T;
 
@@ -98,24 +130,25 @@ impl<T: Component> WorldFetch for &T {
         mapping_table: &MappingTable,
     ) -> Self::Item<'a> {
         unsafe {
-            &*(&components[mapping_table.as_mapping().copied().unwrap()] as *const dyn Any
+            &*(components[mapping_table.as_mapping().copied().unwrap()].as_ref() as *const dyn Any
                 as *const T)
         }
     }
 
     fn contain(components_ids: &mut Vec<TypeId>) -> Option<MappingTable> {
-        let mapping = components_ids.binary_search(&TypeId::of::<T>()).ok()?;
-        components_ids.remove(mapping);
-
-        Some(MappingTable::Mapping(mapping))
+        take_component::<T>(components_ids)
     }
 
+    fn writes(_: &mut Vec<TypeId>) {}
+
     #[cfg(feature = "system")]
     fn alias_conflict(alias_map: &mut AliasMap) {
         alias_map.insert::<Self, T>(crate::system::state::Alias::Imut)
     }
 }
 
+unsafe impl<T: Component> ReadOnlyFetch for &T {}
+
 impl<T: Component> WorldFetch for &'_ mut T {
     type Item<'a> = &'a mut T;
 
@@ -125,17 +158,21 @@ impl<T: Component> WorldFetch for &'_ mut T {
         components: &'a Components,
         mapping_table: &MappingTable,
     ) -> Self::Item<'a> {
-        #[allow(clippy::cast_ref_to_mut)]
+        // Box<dyn Any>本身就是一个指针
+        // 直接读出指针,而不是经过&dyn Any再转换为可变引用
         unsafe {
-            &mut *(&components[mapping_table.as_mapping().copied().unwrap()] as *const dyn Any
-                as *mut T)
+            let component = &components[mapping_table.as_mapping().copied().unwrap()];
+            let ptr = *(component as *const Box<dyn Any> as *const *mut dyn Any);
+            &mut *(ptr as *mut T)
         }
     }
 
     fn contain(components_ids: &mut Vec<TypeId>) -> Option<MappingTable> {
-        let mapping = components_ids.binary_search(&TypeId::of::<T>()).ok()?;
-        components_ids.remove(mapping);
-        Some(MappingTable::Mapping(mapping))
+        take_component::<T>(components_ids)
+    }
+
+    fn writes(ids: &mut Vec<TypeId>) {
+        ids.push(TypeId::of::<T>());
     }
 
     #[cfg(feature = "system")]
@@ -147,7 +184,7 @@ impl<T: Component> WorldFetch for &'_ mut T {
 #[rustfmt::skip]
 mod __impl {
     
-    use super::{Components, MappingTable, TypeId, WorldFetch,};
+    use super::{Components, MappingTable, ReadOnlyFetch, TypeId, WorldFetch,};
     #[cfg(feature = "system")]
     use super::AliasMap;
 
@@ -178,11 +215,17 @@ mod __impl {
                     Some(MappingTable::Node(mappings))
                 }
 
+                fn writes(ids: &mut Vec<TypeId>) {
+                    $($t::writes(ids);)*
+                }
+
                 #[cfg(feature = "system")]
                 fn alias_conflict(alias_map: &mut AliasMap) {
                     $($t::alias_conflict(alias_map);)*
                 }
             }
+
+            unsafe impl<$($t:ReadOnlyFetch),*> ReadOnlyFetch for ($($t,)*){}
         };
     }
     
@@ -4,7 +4,9 @@ use std::{
     marker::PhantomData,
 };
 
-use crate::bundle::BundleMeta;
+use crate::bundle::{BundleMeta, Component};
+#[cfg(feature = "system")]
+use crate::system::state::ArchetypeFilter;
 #[allow(unused_imports)]
 use crate::{bundle::Bundle, tools::WorldFetch};
 /// 用来过滤[Bundle]
@@ -20,10 +22,21 @@ pub trait WorldFilter: Any {
     /// 加速版本,会从缓存读取,否则重新计算
     ///
     /// 主要是为了让嵌套的[WorldFilter]可以更快
-    fn filter_by_meta(meta: &mut BundleMeta) -> bool;
+    fn filter_by_meta(meta: &BundleMeta) -> bool;
+
+    /// 记录一定存在和一定不存在的[Component]
+    ///
+    /// 用来判断两个[Query]是否可能访问同一个[Entity],默认不记录任何信息
+    ///
+    /// [Component]:crate
+    /// [Query]:crate
+    #[cfg(feature = "system")]
+    fn archetype_filter(_filter: &mut ArchetypeFilter) {}
 }
 
-/// [Bundle]是B的子集时通过
+/// [Bundle]包含B中所有的[Component]时通过
+///
+/// [Component]:crate
 #[derive(Debug, Clone, Copy)]
 pub struct All<B: Bundle>(PhantomData<B>);
 
@@ -31,25 +44,59 @@ pub struct All<B: Bundle>(PhantomData<B>);
 #[derive(Debug, Clone, Copy)]
 pub struct AnyOf<B: Bundle>(PhantomData<B>);
 
-/// [Bundle]与B没有交集时通过
+/// [Bundle]包含T时通过
+///
+/// 和`All<T>`相同
+#[derive(Debug, Clone, Copy)]
+pub struct With<T: Component>(PhantomData<T>);
+
+/// [Bundle]不包含T时通过
+///
+/// 和`Not<With<T>>`相同
+#[derive(Debug, Clone, Copy)]
+pub struct Without<T: Component>(PhantomData<T>);
+
+/// [WorldFilter]F不通过时通过
+///
+/// 可以嵌套任意的[WorldFilter],比如[Bundle]与B没有交集时通过: `Not<AnyOf<B>>`
 #[derive(Debug, Clone, Copy)]
-pub struct Not<B: Bundle>(PhantomData<B>);
+pub struct Not<F: WorldFilter>(PhantomData<F>);
+
+/// 由[WorldFilter]构成的元组中任意一个通过时通过
+///
+/// 比如`Or<(All<A>, Not<AnyOf<B>>)>`
+///
+/// 和元组一样,[WorldFilter]构成的元组默认是全部通过时才通过
+///
+/// 所有的[WorldFilter]都只根据[Bundle]的组成过滤,
+/// 没有逐个[Entity]判断的`Changed`或`Added`,所以也无法组合出`Or<(Changed<A>, Added<A>)>`
+///
+/// [Entity]:crate::storage::Entity
+#[derive(Debug, Clone, Copy)]
+pub struct Or<F: WorldFilter>(PhantomData<F>);
 
 impl<B: Bundle> WorldFilter for All<B> {
     fn filter(components_ids: &'static [TypeId]) -> bool {
-        let set = B::components_ids()
+        let set = components_ids
             .iter()
             .copied()
             .fold(HashSet::new(), |mut set, id| {
                 set.insert(id);
                 set
             });
-        !components_ids.iter().any(|id| !set.contains(id))
+        B::components_ids().iter().all(|id| set.contains(id))
     }
 
-    fn filter_by_meta(meta: &mut BundleMeta) -> bool {
+    fn filter_by_meta(meta: &BundleMeta) -> bool {
         meta.filter::<Self>()
     }
+
+    #[cfg(feature = "system")]
+    fn archetype_filter(filter: &mut ArchetypeFilter) {
+        for id in B::components_ids() {
+            filter.with(*id);
+        }
+    }
 }
 
 impl<B: Bundle> WorldFilter for AnyOf<B> {
@@ -64,30 +111,55 @@ impl<B: Bundle> WorldFilter for AnyOf<B> {
         components_ids.iter().any(|id| set.contains(id))
     }
 
-    fn filter_by_meta(meta: &mut BundleMeta) -> bool {
+    fn filter_by_meta(meta: &BundleMeta) -> bool {
         meta.filter::<Self>()
     }
 }
 
-impl<B: Bundle> WorldFilter for Not<B> {
+impl<T: Component> WorldFilter for With<T> {
     fn filter(components_ids: &'static [TypeId]) -> bool {
-        let set = B::components_ids()
-            .iter()
-            .copied()
-            .fold(HashSet::new(), |mut set, id| {
-                set.insert(id);
-                set
-            });
-        !components_ids.iter().any(|id| set.contains(id))
+        components_ids.contains(&T::type_id_())
+    }
+
+    fn filter_by_meta(meta: &BundleMeta) -> bool {
+        meta.filter::<Self>()
+    }
+
+    #[cfg(feature = "system")]
+    fn archetype_filter(filter: &mut ArchetypeFilter) {
+        filter.with(T::type_id_());
+    }
+}
+
+impl<T: Component> WorldFilter for Without<T> {
+    fn filter(components_ids: &'static [TypeId]) -> bool {
+        !components_ids.contains(&T::type_id_())
+    }
+
+    fn filter_by_meta(meta: &BundleMeta) -> bool {
+        meta.filter::<Self>()
+    }
+
+    #[cfg(feature = "system")]
+    fn archetype_filter(filter: &mut ArchetypeFilter) {
+        filter.without(T::type_id_());
+    }
+}
+
+impl<F: WorldFilter> WorldFilter for Not<F> {
+    fn filter(components_ids: &'static [TypeId]) -> bool {
+        !F::filter(components_ids)
     }
 
-    fn filter_by_meta(meta: &mut BundleMeta) -> bool {
+    fn filter_by_meta(meta: &BundleMeta) -> bool {
         meta.filter::<Self>()
     }
 }
 
 mod __impl {
-    use super::{BundleMeta, TypeId, WorldFilter};
+    #[cfg(feature = "system")]
+    use super::ArchetypeFilter;
+    use super::{BundleMeta, Or, TypeId, WorldFilter};
     macro_rules! impl_filter {
         ($($t:ident),*) => {
             impl<$($t:WorldFilter),*> WorldFilter for ($($t,)*) {
@@ -95,22 +167,105 @@ mod __impl {
                     $($t::filter(components_ids))&&*
                 }
 
-                fn filter_by_meta(meta: &mut BundleMeta) -> bool {
+                fn filter_by_meta(meta: &BundleMeta) -> bool {
                    $($t::filter_by_meta(meta))&&*
                 }
+
+                #[cfg(feature = "system")]
+                fn archetype_filter(filter: &mut ArchetypeFilter) {
+                    $($t::archetype_filter(filter);)*
+                }
             }
         };
     }
 
     trecs_proc::all_tuple!(impl_filter, 16);
 
+    macro_rules! impl_or {
+        ($($t:ident),*) => {
+            impl<$($t:WorldFilter),*> WorldFilter for Or<($($t,)*)> {
+                fn filter(components_ids : &'static [TypeId]) -> bool{
+                    $($t::filter(components_ids))||*
+                }
+
+                // 整个表达式的结果也会被缓存
+                fn filter_by_meta(meta: &BundleMeta) -> bool {
+                    meta.filter::<Self>()
+                }
+            }
+        };
+    }
+
+    trecs_proc::all_tuple!(impl_or, 16);
+
     impl WorldFilter for () {
         fn filter(_: &'static [TypeId]) -> bool {
             true
         }
 
-        fn filter_by_meta(_meta: &mut BundleMeta) -> bool {
+        fn filter_by_meta(_meta: &BundleMeta) -> bool {
             true
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use super::{All, AnyOf, Not, Or, With, Without, WorldFilter};
+    use crate::bundle::{Bundle, BundleMeta};
+
+    /// 每种[WorldFilter]在几种不同组成的[Bundle]上的结果
+    fn check<F: WorldFilter>(expected: [bool; 4]) {
+        let shapes: [&'static [TypeId]; 4] = [
+            &[],
+            <(i32,)>::components_ids(),
+            <(i32, u8)>::components_ids(),
+            <(u8, bool, &str)>::components_ids(),
+        ];
+        let result = shapes.map(F::filter);
+        assert_eq!(result, expected, "{}", std::any::type_name::<F>());
+    }
+
+    #[test]
+    fn filters() {
+        check::<()>([true, true, true, true]);
+
+        check::<With<i32>>([false, true, true, false]);
+        check::<With<u8>>([false, false, true, true]);
+        check::<Without<i32>>([true, false, false, true]);
+        check::<(With<u8>, Without<i32>)>([false, false, false, true]);
+
+        check::<All<i32>>([false, true, true, false]);
+        check::<All<(i32, u8)>>([false, false, true, false]);
+        check::<All<(u8, &str)>>([false, false, false, true]);
+
+        check::<AnyOf<i32>>([false, true, true, false]);
+        check::<AnyOf<(i32, bool)>>([false, true, true, true]);
+        check::<AnyOf<u16>>([false, false, false, false]);
+
+        check::<Not<With<i32>>>([true, false, false, true]);
+        check::<Not<AnyOf<(i32, bool)>>>([true, false, false, false]);
+        check::<Not<All<(i32, u8)>>>([true, true, false, true]);
+        check::<Not<Not<With<u8>>>>([false, false, true, true]);
+
+        check::<Or<(With<i32>,)>>([false, true, true, false]);
+        check::<Or<(With<i32>, With<bool>)>>([false, true, true, true]);
+        check::<Or<(All<(i32, u8)>, Without<u8>)>>([true, true, true, false]);
+        check::<Not<Or<(With<i32>, With<u8>)>>>([true, false, false, false]);
+    }
+
+    #[test]
+    fn cache() {
+        // 整个表达式的结果会缓存在BundleMeta中
+        let meta = BundleMeta::new::<(i32, u8)>();
+        type Q = Or<(With<&'static str>, (With<u8>, Without<bool>))>;
+        assert!(Q::filter_by_meta(&meta));
+        assert_eq!(
+            meta.filter_cache.borrow().get(&TypeId::of::<Q>()),
+            Some(&true)
+        );
+        assert!(!Not::<Q>::filter_by_meta(&meta));
+    }
+}
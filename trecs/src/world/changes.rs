@@ -0,0 +1,131 @@
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{storage::Entity, tools::WorldFetch};
+
+/// 一个追踪者上一次取走之后的变化
+#[derive(Debug, Default)]
+pub(crate) struct Changed {
+    /// 被追踪的[Component]可能发生变化的[Entity],包括新增和删除
+    ///
+    /// [Component]:crate
+    pub entities: HashSet<Entity>,
+    /// 被可变的[Query]访问过的原型,其中所有的[Entity]都可能发生了变化
+    pub archetypes: HashSet<usize>,
+}
+
+/// 追踪某种[Component]的变化
+///
+/// [Component]:crate
+#[derive(Debug)]
+struct Tracker {
+    component: TypeId,
+    changed: Changed,
+}
+
+/// 记录被追踪的[Component]的变化
+///
+/// 由[SpatialIndex]和[Index]使用,[World::maintain]时只需要处理变化了的[Entity]
+///
+/// [Query]只持有&[World],所以使用[RefCell]
+///
+/// [Component]:crate
+/// [SpatialIndex]:crate::spatial::SpatialIndex
+/// [Index]:crate::index::Index
+#[derive(Debug, Default)]
+pub(crate) struct Changes {
+    /// 追踪者的[TypeId]对应的[Tracker]
+    trackers: RefCell<HashMap<TypeId, Tracker>>,
+}
+
+impl Changes {
+    /// 开始为tracker追踪component的变化,已经在追踪时什么都不做
+    pub fn track(&self, tracker: TypeId, component: TypeId) {
+        self.trackers
+            .borrow_mut()
+            .entry(tracker)
+            .or_insert_with(|| Tracker {
+                component,
+                changed: Changed::default(),
+            });
+    }
+
+    /// 取走tracker上一次取走之后的变化
+    pub fn take(&self, tracker: TypeId) -> Changed {
+        self.trackers
+            .borrow_mut()
+            .get_mut(&tracker)
+            .map(|tracker| std::mem::take(&mut tracker.changed))
+            .unwrap_or_default()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.trackers.borrow().is_empty()
+    }
+
+    fn mark(&self, component: Option<TypeId>, f: impl Fn(&mut Changed)) {
+        for tracker in self.trackers.borrow_mut().values_mut() {
+            if component.is_none_or(|id| id == tracker.component) {
+                f(&mut tracker.changed);
+            }
+        }
+    }
+
+    /// 记录entity上类型为id的[Component]可能发生了变化
+    ///
+    /// [Component]:crate
+    pub fn mark_changed(&self, entity: Entity, id: TypeId) {
+        self.mark(Some(id), |changed| {
+            changed.entities.insert(entity);
+        });
+    }
+
+    /// 记录entity上所有被追踪的[Component]都可能发生了变化
+    ///
+    /// 用于新增和删除[Entity]
+    ///
+    /// [Component]:crate
+    pub fn mark_all_changed(&self, entities: &[Entity]) {
+        self.mark(None, |changed| {
+            changed.entities.extend(entities);
+        });
+    }
+
+    /// 记录F可写的[Component]在这些原型中都可能发生了变化
+    ///
+    /// [Component]:crate
+    pub fn mark_written<F: WorldFetch>(&self, archetypes: impl Iterator<Item = usize>) {
+        if self.is_empty() {
+            return;
+        }
+        let mut ids = vec![];
+        F::writes(&mut ids);
+        if ids.is_empty() {
+            return;
+        }
+        for archetype in archetypes {
+            for id in &ids {
+                self.mark(Some(*id), |changed| {
+                    changed.archetypes.insert(archetype);
+                });
+            }
+        }
+    }
+
+    /// 记录F可写的[Component]在entity上可能发生了变化
+    ///
+    /// [Component]:crate
+    pub fn mark_fetched<F: WorldFetch>(&self, entity: Entity) {
+        if self.is_empty() {
+            return;
+        }
+        let mut ids = vec![];
+        F::writes(&mut ids);
+        for id in ids {
+            self.mark_changed(entity, id);
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use crate::{tools::Command, World};
+use crate::{tools::Command, world::EntityMut, World};
 
 #[cfg(feature = "system")]
-use crate::system::SystemParm;
+use crate::{
+    error::TrecsError,
+    system::{ReborrowParm, SystemParm},
+};
 
 pub struct Commands<'a> {
     inner: &'a mut World,
@@ -37,17 +40,95 @@ impl Command for Commands<'_> {
     ) -> Option<F::Item<'_>> {
         self.inner.fetch::<F>(entity)
     }
+
+    fn set_parent(
+        &mut self,
+        child: crate::storage::Entity,
+        parent: crate::storage::Entity,
+    ) -> bool {
+        self.inner.set_parent(child, parent)
+    }
+
+    fn remove_parent(&mut self, child: crate::storage::Entity) -> Option<crate::storage::Entity> {
+        self.inner.remove_parent(child)
+    }
+
+    fn despawn_recursive(&mut self, entity: crate::storage::Entity) -> bool {
+        self.inner.despawn_recursive(entity)
+    }
+
+    fn relate<R: crate::bundle::Relation>(
+        &mut self,
+        source: crate::storage::Entity,
+        target: crate::storage::Entity,
+    ) -> bool {
+        self.inner.relate::<R>(source, target)
+    }
+
+    fn unrelate<R: crate::bundle::Relation>(
+        &mut self,
+        source: crate::storage::Entity,
+        target: crate::storage::Entity,
+    ) -> bool {
+        self.inner.unrelate::<R>(source, target)
+    }
+
+    fn targets<R: crate::bundle::Relation>(
+        &self,
+        source: crate::storage::Entity,
+    ) -> &[crate::storage::Entity] {
+        self.inner.targets::<R>(source)
+    }
+
+    fn sources<R: crate::bundle::Relation>(
+        &self,
+        target: crate::storage::Entity,
+    ) -> &[crate::storage::Entity] {
+        self.inner.sources::<R>(target)
+    }
+
+    fn pairs<R: crate::bundle::Relation>(
+        &self,
+    ) -> Vec<(crate::storage::Entity, crate::storage::Entity)> {
+        self.inner.pairs::<R>()
+    }
+}
+
+impl Commands<'_> {
+    /// 获取对[Entity]的可变访问,可以增删[Component]
+    ///
+    /// [Entity]失效时返回[None]
+    ///
+    /// [Entity]: crate::storage::Entity
+    /// [Component]: crate
+    pub fn entity_mut(&mut self, entity: crate::storage::Entity) -> Option<EntityMut<'_>> {
+        self.inner.entity_mut(entity)
+    }
+
+    /// 请求退出,和[World::exit]相同
+    #[cfg(feature = "system")]
+    pub fn exit(&mut self) {
+        self.inner.exit();
+    }
+}
+
+#[cfg(feature = "system")]
+unsafe impl ReborrowParm for Commands<'_> {
+    type Item<'w> = Commands<'w> where Self: 'w;
 }
 
 #[cfg(feature = "system")]
 impl SystemParm for Commands<'_> {
-    unsafe fn build(world: &World) -> Self {
+    type State = ();
+
+    unsafe fn build(world: &World, _state: &mut Self::State) -> Self {
         #[allow(mutable_transmutes)]
         let world: &mut World = std::mem::transmute(world);
         Commands { inner: world }
     }
 
-    fn init(_state: &mut crate::system::state::SystemState) {
+    fn init(_state: &mut crate::system::state::SystemState) -> Result<(), TrecsError> {
         // commands无约束
+        Ok(())
     }
 }
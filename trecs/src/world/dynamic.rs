@@ -0,0 +1,195 @@
+use crate::{
+    bundle::{ComponentDescriptor, ComponentId, RawComponent},
+    error::TrecsError,
+    storage::Entity,
+    World,
+};
+
+use super::Composition;
+
+impl World {
+    /// 在运行时注册一种[Component]
+    ///
+    /// 名字相同时返回已经注册的[ComponentId],
+    /// 但是布局或者Drop函数不同时返回[TrecsError]
+    ///
+    /// [Component]:crate
+    pub fn register_component(
+        &mut self,
+        descriptor: ComponentDescriptor,
+    ) -> Result<ComponentId, TrecsError> {
+        self.dynamic_components.register(descriptor)
+    }
+
+    /// 通过名字找到运行时注册的[ComponentId]
+    pub fn component_id(&self, name: &str) -> Option<ComponentId> {
+        self.dynamic_components.id(name)
+    }
+
+    /// [ComponentId]对应的[Component]的名字
+    ///
+    /// 静态的[Component]在还没有被添加到[World]中时是"?"
+    ///
+    /// [Component]:crate
+    pub(crate) fn component_name(&self, id: ComponentId) -> &'static str {
+        if let Some(name) = self.dynamic_components.name(id) {
+            return name;
+        }
+        self.metas
+            .iter()
+            .find_map(|meta| Some(meta.components_names[meta.position(id)?]))
+            .unwrap_or("?")
+    }
+
+    /// 运行时注册的[ComponentId]对应的[ComponentDescriptor]
+    pub fn component_descriptor(&self, id: ComponentId) -> Option<&ComponentDescriptor> {
+        self.dynamic_components.get(id)
+    }
+
+    /// 将bytes作为运行时注册的[Component]加到[Entity]上
+    ///
+    /// 已经存在的[Component]会被替换
+    ///
+    /// + [Entity]失效,id没有注册,或者bytes的长度和布局不一致时返回false,什么都不做
+    ///
+    /// [Component]:crate
+    pub fn insert_bytes(&mut self, entity: Entity, id: ComponentId, bytes: &[u8]) -> bool {
+        let Some(descriptor) = self.dynamic_components.get(id) else {
+            return false;
+        };
+        if descriptor.layout().size() != bytes.len() {
+            return false;
+        }
+        let Some(location) = self.locate(entity) else {
+            return false;
+        };
+        let component = Box::new(RawComponent::new(descriptor, bytes));
+
+        let chunk = &mut self.chunks[location.chunk_index()];
+        let meta = &self.metas[chunk.meta()];
+        if let Some(idx) = meta.position(id) {
+            let components = unsafe { chunk.get_mut(location.index_in_chunk()) };
+            components[idx] = component;
+            return true;
+        }
+
+        let mut composition = Composition::new(meta);
+        let mut components = chunk.take(location.index_in_chunk());
+        composition.dynamic_ids.push(id);
+        components.push(component);
+        self.relocate(entity, location, &composition, components);
+        true
+    }
+
+    /// 获取[Entity]上运行时注册的[Component]的字节
+    ///
+    /// [Component]:crate
+    pub fn get_bytes(&self, entity: Entity, id: ComponentId) -> Option<&[u8]> {
+        let location = self.locate(entity)?;
+        let chunk = &self.chunks[location.chunk_index()];
+        let idx = self.metas[chunk.meta()].position(id)?;
+        let components = unsafe { chunk.get(location.index_in_chunk()) };
+        Some(components[idx].downcast_ref::<RawComponent>()?.bytes())
+    }
+
+    /// 获取[Entity]上运行时注册的[Component]的字节的可变引用
+    ///
+    /// [Component]:crate
+    pub fn get_bytes_mut(&mut self, entity: Entity, id: ComponentId) -> Option<&mut [u8]> {
+        let location = self.locate(entity)?;
+        let chunk = &mut self.chunks[location.chunk_index()];
+        let idx = self.metas[chunk.meta()].position(id)?;
+        let components = unsafe { chunk.get_mut(location.index_in_chunk()) };
+        Some(components[idx].downcast_mut::<RawComponent>()?.bytes_mut())
+    }
+
+    /// 从[Entity]上删除[ComponentId]对应的[Component]
+    ///
+    /// 静态和运行时注册的[Component]都可以删除
+    ///
+    /// 返回[Component]是否存在
+    ///
+    /// [Component]:crate
+    pub fn remove_by_id(&mut self, entity: Entity, id: ComponentId) -> bool {
+        self.detach_by_id(entity, id).is_some()
+    }
+
+    /// 遍历所有拥有运行时注册的[Component]的[Entity],以及[Component]的字节
+    ///
+    /// 静态的[ComponentId]不会产生任何结果
+    ///
+    /// [Component]:crate
+    pub fn iter_bytes(&self, id: ComponentId) -> impl Iterator<Item = (Entity, &[u8])> {
+        self.metas
+            .iter()
+            .filter(move |_| id.is_dynamic())
+            .filter_map(move |meta| Some((meta, meta.position(id)?)))
+            .flat_map(move |(meta, idx)| {
+                meta.chunks.iter().flat_map(move |&cid| {
+                    let mut iter = self.chunks[cid].iter();
+                    std::iter::from_fn(move || {
+                        let components = iter.next()?;
+                        let raw = components[idx].downcast_ref::<RawComponent>()?;
+                        Some((iter.chunk.gen_entity(iter.index), raw.bytes()))
+                    })
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::Layout;
+
+    use crate::{
+        bundle::{ComponentDescriptor, ComponentId},
+        tools::Command,
+        World,
+    };
+
+    #[test]
+    fn dynamic_component() {
+        let mut world = World::new();
+        let hp = world
+            .register_component(ComponentDescriptor::new("hp", Layout::new::<u32>()))
+            .unwrap();
+        let tag = world
+            .register_component(ComponentDescriptor::new("tag", Layout::new::<()>()))
+            .unwrap();
+        assert_eq!(world.component_id("hp"), Some(hp));
+        assert_eq!(world.component_descriptor(tag).unwrap().name(), "tag");
+
+        let e1 = world.spawn(1usize);
+        let e2 = world.spawn(2usize);
+        assert!(!world.insert_bytes(e1, hp, &[0; 2]));
+        assert!(world.insert_bytes(e1, hp, &10u32.to_ne_bytes()));
+        assert!(world.insert_bytes(e2, hp, &20u32.to_ne_bytes()));
+        assert!(world.insert_bytes(e2, tag, &[]));
+
+        // 静态的Component不受影响
+        assert_eq!(world.get::<usize>(e2), Some(&2));
+        world.attach(e2, 1u8);
+        assert_eq!(world.get::<u8>(e2), Some(&1));
+        assert_eq!(world.get_bytes(e2, hp), Some(&20u32.to_ne_bytes()[..]));
+
+        world
+            .get_bytes_mut(e1, hp)
+            .unwrap()
+            .copy_from_slice(&11u32.to_ne_bytes());
+        let mut all = world
+            .iter_bytes(hp)
+            .map(|(e, bytes)| (e, u32::from_ne_bytes(bytes.try_into().unwrap())))
+            .collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, vec![(e1, 11), (e2, 20)]);
+        assert_eq!(world.iter_bytes(tag).count(), 1);
+
+        assert!(world.remove_by_id(e2, hp));
+        assert!(!world.remove_by_id(e2, hp));
+        assert!(world.remove_by_id(e2, ComponentId::of::<u8>()));
+        assert_eq!(world.get_bytes(e2, hp), None);
+        assert_eq!(world.get_bytes(e2, tag), Some(&[][..]));
+        assert_eq!(world.get::<usize>(e2), Some(&2));
+        assert_eq!(world.iter_bytes(hp).count(), 1);
+    }
+}
@@ -0,0 +1,425 @@
+use std::{any::Any, marker::PhantomData};
+
+use crate::{
+    bundle::{BundleMeta, ComponentId, Components, RawComponent},
+    error::TrecsError,
+    storage::{Chunk, Entity},
+    World,
+};
+
+/// [QueryBuilder]中对一种[Component]的要求
+///
+/// [Component]:crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// 读取
+    Read,
+    /// 读写
+    Write,
+    /// 只要求存在,不读取
+    With,
+    /// 要求不存在
+    Without,
+}
+
+impl Access {
+    /// 在[QueryBuilder]中对应的方法名
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::With => "with",
+            Self::Without => "without",
+        }
+    }
+}
+
+/// 在运行时构建[DynamicQuery]
+///
+/// 和[Query]不同,[Component]由[ComponentId]指定,不需要在编译时知道类型
+///
+/// ```ignore
+/// let mut query = QueryBuilder::new()
+///     .write(ComponentId::of::<Pos>())
+///     .read(hp)
+///     .without(ComponentId::of::<Dead>())
+///     .build(&world)?;
+/// for mut row in query.iter(&mut world) {
+///     let pos = row.ptr_mut(0).unwrap() as *mut Pos;
+///     let hp = row.bytes(1).unwrap();
+/// }
+/// ```
+///
+/// [Query]:crate
+/// [Component]:crate
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    terms: Vec<(ComponentId, Access)>,
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加一个要求
+    ///
+    /// 读写的[Component]会按照添加的顺序出现在[DynamicRow]中
+    ///
+    /// [Component]:crate
+    pub fn term(mut self, id: impl Into<ComponentId>, access: Access) -> Self {
+        self.terms.push((id.into(), access));
+        self
+    }
+
+    pub fn read(self, id: impl Into<ComponentId>) -> Self {
+        self.term(id, Access::Read)
+    }
+
+    pub fn write(self, id: impl Into<ComponentId>) -> Self {
+        self.term(id, Access::Write)
+    }
+
+    pub fn with(self, id: impl Into<ComponentId>) -> Self {
+        self.term(id, Access::With)
+    }
+
+    pub fn without(self, id: impl Into<ComponentId>) -> Self {
+        self.term(id, Access::Without)
+    }
+
+    /// 生成[DynamicQuery],并匹配[World]中已经存在的[BundleMeta]
+    ///
+    /// 同一种[Component]被写入的同时不能再有其他要求,否则返回[TrecsError]
+    ///
+    /// [BundleMeta]:crate
+    /// [Component]:crate
+    pub fn build(self, world: &World) -> Result<DynamicQuery, TrecsError> {
+        for (idx, (id, access)) in self.terms.iter().enumerate() {
+            let conflict = self.terms[idx + 1..].iter().find(|(other, other_access)| {
+                other == id && (*access == Access::Write || *other_access == Access::Write)
+            });
+            if let Some((_, other_access)) = conflict {
+                return Err(TrecsError::AliasConflict {
+                    component: world.component_name(*id),
+                    first: access.name(),
+                    first_mut: *access == Access::Write,
+                    second: other_access.name(),
+                    second_mut: *other_access == Access::Write,
+                });
+            }
+        }
+
+        let fetches = self
+            .terms
+            .iter()
+            .filter(|(_, access)| matches!(access, Access::Read | Access::Write))
+            .map(|(id, access)| (*id, *access == Access::Write))
+            .collect();
+        let mut query = DynamicQuery {
+            world: world.id(),
+            terms: self.terms,
+            fetches,
+            matched: vec![],
+            generation: 0,
+        };
+        query.update(world);
+        Ok(query)
+    }
+}
+
+/// 由[QueryBuilder]生成的查询
+///
+/// 会记住已经匹配过的[BundleMeta],每次只检查新出现的[BundleMeta]
+///
+/// 只能用于生成它的[World]
+///
+/// [BundleMeta]:crate
+#[derive(Debug, Clone)]
+pub struct DynamicQuery {
+    /// 生成它的[World]的[World::id]
+    world: usize,
+    terms: Vec<(ComponentId, Access)>,
+    /// 需要读写的[ComponentId],以及是否可写
+    fetches: Vec<(ComponentId, bool)>,
+    /// 匹配的[BundleMeta]的下标,以及读写的[Component]在[Components]中的下标
+    ///
+    /// [Component]:crate
+    matched: Vec<(usize, Vec<usize>)>,
+    /// 已经检查过的[BundleMeta]的数量
+    generation: usize,
+}
+
+impl DynamicQuery {
+    /// 检查新出现的[BundleMeta]
+    ///
+    /// 匹配的结果会缓存在[BundleMeta]中,要求相同的[DynamicQuery]不需要重新计算
+    ///
+    /// world不是生成它的[World]时会panic
+    ///
+    /// [BundleMeta]:crate
+    pub fn update(&mut self, world: &World) {
+        assert_eq!(
+            self.world,
+            world.id(),
+            "a DynamicQuery can only be used with the World that built it"
+        );
+        for (idx, meta) in world.metas.iter().enumerate().skip(self.generation) {
+            if let Some(mapping) = meta.dynamic_fetch(&self.terms) {
+                self.matched.push((idx, mapping));
+            }
+        }
+        self.generation = world.archetype_generation();
+    }
+
+    /// 读写的[ComponentId],和[DynamicRow]中的下标一一对应
+    pub fn fetches(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.fetches.iter().map(|(id, _)| *id)
+    }
+
+    /// 遍历所有满足要求的[Entity]
+    ///
+    /// world不是生成它的[World]时会panic
+    pub fn iter<'a>(&'a mut self, world: &'a mut World) -> DynamicIter<'a> {
+        self.update(world);
+        DynamicIter {
+            metas: &world.metas,
+            chunks: world.chunks.as_mut_ptr(),
+            matched: &self.matched,
+            fetches: &self.fetches,
+            meta: 0,
+            chunk: 0,
+            index: 0,
+            _p: PhantomData,
+        }
+    }
+}
+
+/// [DynamicQuery]的迭代器
+pub struct DynamicIter<'a> {
+    metas: &'a [BundleMeta],
+    /// [World]中所有的[Chunk],每一行只从中借出自己的[Components]
+    chunks: *mut Chunk,
+    matched: &'a [(usize, Vec<usize>)],
+    fetches: &'a [(ComponentId, bool)],
+    meta: usize,
+    chunk: usize,
+    index: usize,
+    _p: PhantomData<&'a mut Chunk>,
+}
+
+impl<'a> Iterator for DynamicIter<'a> {
+    type Item = DynamicRow<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (meta, mapping) = self.matched.get(self.meta)?;
+            let Some(&cid) = self.metas[*meta].chunks.get(self.chunk) else {
+                self.meta += 1;
+                self.chunk = 0;
+                continue;
+            };
+            // 只借用Chunk本身,之前返回的行借用的是Chunk中其他位置的Components
+            let chunk = unsafe { &mut *self.chunks.add(cid) };
+            if self.index >= chunk.len() {
+                self.chunk += 1;
+                self.index = 0;
+                continue;
+            }
+
+            let index = self.index;
+            self.index += 1;
+            if !chunk.occupied(index) {
+                continue;
+            }
+            // 每一行都是不同位置的Components,所以不会产生别名
+            return Some(DynamicRow {
+                entity: chunk.gen_entity(index),
+                components: unsafe { &mut *chunk.components_ptr(index) },
+                mapping,
+                fetches: self.fetches,
+            });
+        }
+    }
+}
+
+/// [DynamicQuery]中的一行
+///
+/// 通过读写的[Component]在[QueryBuilder]中的顺序访问
+///
+/// [Component]:crate
+pub struct DynamicRow<'a> {
+    entity: Entity,
+    components: &'a mut Components,
+    mapping: &'a [usize],
+    fetches: &'a [(ComponentId, bool)],
+}
+
+impl<'a> DynamicRow<'a> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// 读写的[Component]的数量
+    ///
+    /// [Component]:crate
+    pub fn len(&self) -> usize {
+        self.mapping.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mapping.is_empty()
+    }
+
+    /// 第i个[Component]的指针
+    ///
+    /// [Component]:crate
+    pub fn ptr(&self, i: usize) -> *const u8 {
+        let component = &self.components[self.mapping[i]];
+        match component.downcast_ref::<RawComponent>() {
+            Some(raw) if self.fetches[i].0.is_dynamic() => raw.bytes().as_ptr(),
+            _ => component.as_ref() as *const dyn Any as *const u8,
+        }
+    }
+
+    /// 第i个[Component]的可变指针
+    ///
+    /// 只读的[Component]返回[None]
+    ///
+    /// [Component]:crate
+    pub fn ptr_mut(&mut self, i: usize) -> Option<*mut u8> {
+        let (id, write) = self.fetches[i];
+        if !write {
+            return None;
+        }
+        let component = &mut self.components[self.mapping[i]];
+        if id.is_dynamic() {
+            let raw = component.downcast_mut::<RawComponent>()?;
+            return Some(raw.bytes_mut().as_mut_ptr());
+        }
+        Some(component.as_mut() as *mut dyn Any as *mut u8)
+    }
+
+    /// 第i个运行时注册的[Component]的字节
+    ///
+    /// 静态的[Component]返回[None]
+    ///
+    /// [Component]:crate
+    pub fn bytes(&self, i: usize) -> Option<&[u8]> {
+        let component = &self.components[self.mapping[i]];
+        Some(component.downcast_ref::<RawComponent>()?.bytes())
+    }
+
+    /// 第i个运行时注册的[Component]的字节的可变引用
+    ///
+    /// 静态的或者只读的[Component]返回[None]
+    ///
+    /// [Component]:crate
+    pub fn bytes_mut(&mut self, i: usize) -> Option<&mut [u8]> {
+        if !self.fetches[i].1 {
+            return None;
+        }
+        let component = &mut self.components[self.mapping[i]];
+        Some(component.downcast_mut::<RawComponent>()?.bytes_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::Layout;
+
+    use super::QueryBuilder;
+    use crate::{
+        bundle::{ComponentDescriptor, ComponentId},
+        error::TrecsError,
+        tools::Command,
+        World,
+    };
+
+    #[test]
+    fn dynamic_query() {
+        let mut world = World::new();
+        let hp = world
+            .register_component(ComponentDescriptor::new("hp", Layout::new::<u32>()))
+            .unwrap();
+        let e1 = world.spawn((1usize, 1u8));
+        let e2 = world.spawn(2usize);
+        let e3 = world.spawn(3usize);
+        world.insert_bytes(e2, hp, &20u32.to_ne_bytes());
+        world.insert_bytes(e3, hp, &30u32.to_ne_bytes());
+
+        let mut query = QueryBuilder::new()
+            .write(ComponentId::of::<usize>())
+            .read(hp)
+            .without(ComponentId::of::<u8>())
+            .build(&world)
+            .unwrap();
+        let mut entities = vec![];
+        for mut row in query.iter(&mut world) {
+            assert!(row.bytes_mut(1).is_none());
+            let hp = u32::from_ne_bytes(row.bytes(1).unwrap().try_into().unwrap());
+            let value = row.ptr_mut(0).unwrap() as *mut usize;
+            unsafe { *value += hp as usize };
+            entities.push(row.entity());
+        }
+        entities.sort();
+        assert_eq!(entities, vec![e2, e3]);
+        assert_eq!(world.get::<usize>(e2), Some(&22));
+        assert_eq!(world.get::<usize>(e1), Some(&1));
+
+        // 匹配的结果缓存在BundleMeta中
+        assert!(world
+            .metas
+            .iter()
+            .all(|meta| meta.dynamic_cache.borrow().len() == 1));
+
+        // 新出现的BundleMeta也会被匹配
+        world.insert_bytes(e1, hp, &10u32.to_ne_bytes());
+        world.detach::<u8>(e1);
+        assert_eq!(query.iter(&mut world).count(), 3);
+
+        let mut query = QueryBuilder::new().with(hp).build(&world).unwrap();
+        assert!(query.iter(&mut world).all(|row| row.is_empty()));
+        assert_eq!(
+            QueryBuilder::new()
+                .read(ComponentId::of::<u8>())
+                .build(&world)
+                .unwrap()
+                .iter(&mut world)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn conflict() {
+        let mut world = World::new();
+        world.spawn(1usize);
+        let error = QueryBuilder::new()
+            .write(ComponentId::of::<usize>())
+            .read(ComponentId::of::<usize>())
+            .build(&world)
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TrecsError::AliasConflict {
+                component: "usize",
+                first: "write",
+                first_mut: true,
+                second: "read",
+                second_mut: false,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "World that built it")]
+    fn other_world() {
+        let world = World::new();
+        let mut query = QueryBuilder::new()
+            .read(ComponentId::of::<usize>())
+            .build(&world)
+            .unwrap();
+        query.iter(&mut World::new()).count();
+    }
+}
@@ -0,0 +1,321 @@
+use std::any::TypeId;
+
+use crate::{
+    bundle::{Bundle, Component, ComponentId},
+    storage::Entity,
+    tools::Command,
+    World,
+};
+
+/// [Entity]所属的原型,即[BundleMeta]的下标
+///
+/// 组成相同的[Entity]属于同一个原型
+///
+/// [BundleMeta]:crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArchetypeId(pub(crate) usize);
+
+impl ArchetypeId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/// 对一个[Entity]的只读访问
+///
+/// 不需要知道[Entity]的具体组成
+///
+/// 通过[World::entity]获得
+#[derive(Clone, Copy)]
+pub struct EntityRef<'a> {
+    world: &'a World,
+    entity: Entity,
+    archetype: usize,
+}
+
+impl<'a> EntityRef<'a> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// [Entity]所属的原型
+    pub fn archetype(&self) -> ArchetypeId {
+        ArchetypeId(self.archetype)
+    }
+
+    /// [Entity]上是否存在类型为T的[Component]
+    pub fn contains<T: Component>(&self) -> bool {
+        self.contains_id(T::type_id_())
+    }
+
+    /// [Entity]上是否存在[ComponentId]对应的[Component]
+    ///
+    /// 可以直接传入[TypeId]
+    pub fn contains_id(&self, id: impl Into<ComponentId>) -> bool {
+        self.world.metas[self.archetype]
+            .position(id.into())
+            .is_some()
+    }
+
+    /// 获取[Entity]上类型为T的[Component]
+    pub fn get<T: Component>(&self) -> Option<&'a T> {
+        self.world.get(self.entity)
+    }
+
+    /// [Entity]上所有[Component]的[TypeId]
+    pub fn component_ids(&self) -> &'a [TypeId] {
+        self.world.metas[self.archetype].components_ids
+    }
+
+    /// [Entity]上所有[Component]的类型名,和component_ids一一对应
+    pub fn component_names(&self) -> &'a [&'static str] {
+        self.world.metas[self.archetype].components_names
+    }
+
+    /// [Entity]上所有运行时注册的[Component]的[ComponentId]
+    pub fn dynamic_ids(&self) -> &'a [ComponentId] {
+        self.world.metas[self.archetype].dynamic_ids
+    }
+
+    /// 获取[Entity]上运行时注册的[Component]的字节
+    pub fn get_bytes(&self, id: ComponentId) -> Option<&'a [u8]> {
+        self.world.get_bytes(self.entity, id)
+    }
+}
+
+impl std::fmt::Debug for EntityRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntityRef")
+            .field("entity", &self.entity)
+            .field("components", &self.component_names())
+            .field("dynamic", &self.dynamic_ids())
+            .finish()
+    }
+}
+
+/// 对一个[Entity]的读写访问
+///
+/// 可以增删[Component],[Entity]本身不会失效
+///
+/// 通过[World::entity_mut]获得
+pub struct EntityMut<'a> {
+    world: &'a mut World,
+    entity: Entity,
+}
+
+impl<'a> EntityMut<'a> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// 转为只读访问
+    pub fn as_readonly(&self) -> EntityRef<'_> {
+        // 增删Component之后原型会变化,所以每次重新查找
+        self.world.entity(self.entity).unwrap()
+    }
+
+    /// [Entity]所属的原型
+    pub fn archetype(&self) -> ArchetypeId {
+        self.as_readonly().archetype()
+    }
+
+    /// [Entity]上是否存在类型为T的[Component]
+    pub fn contains<T: Component>(&self) -> bool {
+        self.as_readonly().contains::<T>()
+    }
+
+    /// 获取[Entity]上类型为T的[Component]
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.world.get(self.entity)
+    }
+
+    /// 获取[Entity]上类型为T的[Component]的可变引用
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.world.get_mut(self.entity)
+    }
+
+    /// [Entity]上所有[Component]的[TypeId]
+    pub fn component_ids(&self) -> &[TypeId] {
+        self.as_readonly().component_ids()
+    }
+
+    /// [Entity]上所有[Component]的类型名,和component_ids一一对应
+    pub fn component_names(&self) -> &[&'static str] {
+        self.as_readonly().component_names()
+    }
+
+    /// [Entity]上所有运行时注册的[Component]的[ComponentId]
+    pub fn dynamic_ids(&self) -> &[ComponentId] {
+        self.as_readonly().dynamic_ids()
+    }
+
+    /// 获取[Entity]上运行时注册的[Component]的字节
+    pub fn get_bytes(&self, id: ComponentId) -> Option<&[u8]> {
+        self.world.get_bytes(self.entity, id)
+    }
+
+    /// 获取[Entity]上运行时注册的[Component]的字节的可变引用
+    pub fn get_bytes_mut(&mut self, id: ComponentId) -> Option<&mut [u8]> {
+        self.world.get_bytes_mut(self.entity, id)
+    }
+
+    /// 将[Bundle]中的[Component]加到[Entity]上
+    ///
+    /// 已经存在的[Component]会被替换
+    pub fn insert<B: Bundle>(&mut self, b: B) -> &mut Self {
+        self.world.attach(self.entity, b);
+        self
+    }
+
+    /// 从[Entity]上拿走类型为T的[Component]
+    ///
+    /// 不存在时返回[None]
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        self.world.detach(self.entity)
+    }
+
+    /// 从[World]中删除[Entity],和[Command::remove]相同
+    pub fn despawn(self) {
+        self.world.remove(self.entity);
+    }
+}
+
+impl std::fmt::Debug for EntityMut<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_readonly().fmt(f)
+    }
+}
+
+impl World {
+    /// 获取对[Entity]的只读访问
+    ///
+    /// [Entity]失效时返回[None]
+    pub fn entity(&self, entity: Entity) -> Option<EntityRef<'_>> {
+        let location = self.locate(entity)?;
+        let archetype = self.chunks[location.chunk_index()].meta();
+        Some(EntityRef {
+            world: self,
+            entity,
+            archetype,
+        })
+    }
+
+    /// 获取对[Entity]的读写访问
+    ///
+    /// [Entity]失效时返回[None]
+    pub fn entity_mut(&mut self, entity: Entity) -> Option<EntityMut<'_>> {
+        self.locate(entity)?;
+        Some(EntityMut {
+            world: self,
+            entity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use crate::{bundle::Component, tools::Command, World};
+
+    #[derive(Debug, PartialEq)]
+    struct A(i32);
+
+    impl Component for A {
+        fn type_id_() -> TypeId {
+            TypeId::of::<Self>()
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct B(&'static str);
+
+    impl Component for B {
+        fn type_id_() -> TypeId {
+            TypeId::of::<Self>()
+        }
+    }
+
+    #[test]
+    fn entity_ref() {
+        let mut world = World::new();
+        let e1 = world.spawn((A(1), B("b")));
+        let e2 = world.spawn(A(2));
+
+        let r1 = world.entity(e1).unwrap();
+        assert!(r1.contains::<A>() && r1.contains::<B>());
+        assert_eq!(r1.get::<B>(), Some(&B("b")));
+        assert_eq!(r1.component_ids().len(), 2);
+        assert!(r1.component_names()[1].ends_with("B"));
+
+        let r2 = world.entity(e2).unwrap();
+        assert!(!r2.contains::<B>());
+        assert_eq!(r2.get::<B>(), None);
+        assert_ne!(r1.archetype(), r2.archetype());
+
+        world.remove(e2);
+        assert!(world.entity(e2).is_none());
+    }
+
+    #[test]
+    fn entity_mut() {
+        let mut world = World::new();
+        let e1 = world.spawn(A(1));
+        let e2 = world.spawn((A(2), B("b")));
+
+        let mut m = world.entity_mut(e1).unwrap();
+        m.get_mut::<A>().unwrap().0 = 10;
+        m.insert(B("new"));
+        assert_eq!(m.get::<A>(), Some(&A(10)));
+        assert_eq!(m.get::<B>(), Some(&B("new")));
+        // 组成相同时属于同一个原型
+        let archetype = m.archetype();
+        assert_eq!(world.entity(e2).unwrap().archetype(), archetype);
+
+        let mut m = world.entity_mut(e1).unwrap();
+        assert_eq!(m.remove::<A>(), Some(A(10)));
+        assert_eq!(m.remove::<A>(), None);
+        assert_eq!(m.component_ids().len(), 1);
+
+        m.despawn();
+        assert!(world.entity(e1).is_none());
+        assert!(world.entity(e2).is_some());
+    }
+
+    #[test]
+    fn manual_bundle() {
+        use crate::bundle::{Bundle, Components};
+
+        /// 手动实现的[Bundle],没有实现components_names
+        struct Pair(A, B);
+
+        impl Bundle for Pair {
+            fn destory(self) -> Components {
+                vec![Box::new(self.0), Box::new(self.1)]
+            }
+
+            fn components_ids() -> &'static [TypeId] {
+                static IDS: std::sync::OnceLock<[TypeId; 2]> = std::sync::OnceLock::new();
+                IDS.get_or_init(|| [TypeId::of::<A>(), TypeId::of::<B>()])
+            }
+
+            fn drop(cs: Components) {
+                std::mem::drop(cs);
+            }
+
+            fn type_name() -> &'static str {
+                std::any::type_name::<Self>()
+            }
+
+            fn type_id_() -> TypeId {
+                TypeId::of::<Self>()
+            }
+        }
+
+        let mut world = World::new();
+        let entity = world.spawn(Pair(A(1), B("b")));
+        let r = world.entity(entity).unwrap();
+        assert_eq!(r.get::<A>(), Some(&A(1)));
+        assert_eq!(r.component_names(), &["?", "?"]);
+    }
+}
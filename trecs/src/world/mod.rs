@@ -2,20 +2,37 @@ use std::{
     any::{Any, TypeId},
     cell::UnsafeCell,
     collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
+mod changes;
 mod commands;
+mod dynamic;
+mod dynamic_query;
+mod entity;
+#[cfg(feature = "system")]
+mod param_set;
 mod query;
 mod resources;
 
+#[cfg(feature = "system")]
+pub use self::param_set::ParamSet;
+pub(crate) use self::changes::Changes;
 pub use self::{
     commands::Commands,
-    query::Query,
+    dynamic_query::{Access, DynamicIter, DynamicQuery, DynamicRow, QueryBuilder},
+    entity::{ArchetypeId, EntityMut, EntityRef},
+    query::{Query, QuerySingleError, QueryState},
     resources::{Res, Resources},
 };
 
 use crate::{
-    bundle::{Bundle, BundleMeta},
+    bundle::{
+        Bundle, BundleMeta, Component, ComponentId, Components, DynamicComponents, Relation,
+        Relations,
+    },
+    hierarchy::{Children, Parent},
+    index::Indices,
     storage::{Chunk, Entity, CHUNK_SIZE},
     tools::{Command, ResManager},
 };
@@ -27,166 +44,660 @@ type AnRes = UnsafeCell<Option<Box<dyn Any>>>;
 
 type Droper = Option<Box<dyn FnOnce(&mut AnRes)>>;
 
+#[cfg(feature = "async")]
+use std::{future::Future, rc::Rc};
+
 #[cfg(feature = "system")]
-use crate::system::{InnerSystem, System};
+use crate::{
+    app::AppExit,
+    error::{SystemError, TrecsError},
+    system::{InnerSystem, System, SystemOutput},
+};
+#[cfg(feature = "async")]
+use crate::system::{AsyncShared, AsyncTask};
+
+/// 一个[BundleMeta]由哪些[Component]组成
+///
+/// 用于在增删[Component]时找到新的[BundleMeta]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Composition {
+    pub ids: Vec<TypeId>,
+    pub names: Vec<&'static str>,
+    pub dynamic_ids: Vec<ComponentId>,
+}
+
+impl Composition {
+    pub fn new(meta: &BundleMeta) -> Self {
+        Self {
+            ids: meta.components_ids.to_vec(),
+            names: meta.components_names.to_vec(),
+            dynamic_ids: meta.dynamic_ids.to_vec(),
+        }
+    }
+
+    /// 删除在[Components]中下标为idx的[Component]
+    pub fn remove(&mut self, idx: usize) {
+        if idx < self.ids.len() {
+            self.ids.remove(idx);
+            self.names.remove(idx);
+        } else {
+            self.dynamic_ids.remove(idx - self.ids.len());
+        }
+    }
+}
 
 pub struct World {
+    /// 每个[World]都不同的编号,见[World::id]
+    id: usize,
     pub(crate) chunks: Vec<Chunk>,
-    pub(crate) metas: HashMap<TypeId, BundleMeta>,
+    pub(crate) metas: Vec<BundleMeta>,
+    /// [Bundle]的[TypeId]对应的[BundleMeta]的下标
+    pub(crate) bundles: HashMap<TypeId, usize>,
+    /// 由(components_ids,dynamic_ids)对应的[BundleMeta]的下标
+    ///
+    /// 组成相同的[Bundle]会共用同一个[BundleMeta]
+    pub(crate) archetypes: HashMap<(&'static [TypeId], &'static [ComponentId]), usize>,
+    /// 运行时注册的[Component]
+    pub(crate) dynamic_components: DynamicComponents,
+    /// [Entity]之间的所有[Relation]
+    pub(crate) relations: Relations,
     #[cfg(feature = "system")]
     pub(crate) startup_systems: Vec<System>,
+    /// 在[World::run]返回前执行一次的[System]
+    #[cfg(feature = "system")]
+    pub(crate) shutdown_systems: Vec<System>,
     #[cfg(feature = "system")]
     pub(crate) systems: Vec<System>,
+    /// 是否正在[World::run_once]中执行systems或者轮询异步任务
+    #[cfg(feature = "system")]
+    pub(crate) running: bool,
     pub(crate) resources: HashMap<TypeId, AnRes>,
     /// 因为运行时反射 资源在最后都以[Box<dyn Any>]的状态[Drop]
     /// 而不是调用自身的[Drop::drop]和方法
     ///
     /// 所以在创建每一个资源时都记录下一个函数用来Drop
     pub(crate) resources_dropers: HashMap<TypeId, Droper>,
+    /// 按照[Component]的值查找[Entity]的索引
+    pub(crate) indices: Indices,
+    /// 被追踪的[Component]的变化
+    pub(crate) changes: Changes,
+    /// 在[World::maintain]时调用,用来更新索引等资源
+    pub(crate) maintainers: Vec<fn(&mut World)>,
+    /// 处理[System]返回的错误
+    #[cfg(feature = "system")]
+    pub(crate) error_handler: fn(&mut World, SystemError),
+    /// 通过[World::spawn_async]添加的异步任务
+    #[cfg(feature = "async")]
+    pub(crate) async_tasks: Vec<AsyncTask>,
+    #[cfg(feature = "async")]
+    pub(crate) async_shared: Rc<AsyncShared>,
 }
 
 impl World {
     pub fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
         Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             chunks: vec![],
-            metas: Default::default(),
+            metas: vec![],
+            bundles: Default::default(),
+            archetypes: Default::default(),
+            relations: Default::default(),
+            dynamic_components: Default::default(),
             #[cfg(feature = "system")]
             startup_systems: vec![],
             #[cfg(feature = "system")]
+            shutdown_systems: vec![],
+            #[cfg(feature = "system")]
             systems: vec![],
+            #[cfg(feature = "system")]
+            running: false,
             resources: Default::default(),
             resources_dropers: Default::default(),
+            indices: Default::default(),
+            changes: Default::default(),
+            maintainers: vec![],
+            #[cfg(feature = "system")]
+            error_handler: crate::error::handler::panic,
+            #[cfg(feature = "async")]
+            async_tasks: vec![],
+            #[cfg(feature = "async")]
+            async_shared: Default::default(),
+        }
+    }
+
+    /// 区分不同[World]的编号
+    ///
+    /// 用来检查[DynamicQuery]等是否用于生成它的[World]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// 更新所有由[World]维护的资源,比如[SpatialIndex]和[Index]
+    ///
+    /// run_once开始时会自动调用
+    ///
+    /// [SpatialIndex]: crate::spatial::SpatialIndex
+    /// [Index]: crate::index::Index
+    pub fn maintain(&mut self) {
+        for maintainer in self.maintainers.clone() {
+            maintainer(self);
         }
     }
 
     /// 创建一个新的区块,并且返回它的可变引用
     ///
     /// 防止诸如"meta和实际不一致","chunk.index不正确"等错位问题
-    pub(crate) fn new_chunk<B: Bundle>(&mut self) -> &mut Chunk {
-        self.metas
-            .get_mut(&B::type_id_())
-            .unwrap()
-            .chunks
-            .push(self.chunks.len());
-        self.chunks.push(Chunk::new::<B>(self.chunks.len()));
+    pub(crate) fn new_chunk(&mut self, meta: usize) -> &mut Chunk {
+        self.metas[meta].chunks.push(self.chunks.len());
+        self.chunks.push(Chunk::new(self.chunks.len(), meta));
         self.chunks.last_mut().unwrap()
     }
+
+    /// 获取[Bundle]对应的[BundleMeta]的下标
+    ///
+    /// 如果[BundleMeta]不存在,就注册它
+    pub(crate) fn meta_index<B: Bundle>(&mut self) -> usize {
+        if let Some(&meta) = self.bundles.get(&B::type_id_()) {
+            return meta;
+        }
+        let key = (B::components_ids(), &[][..]);
+        let meta = match self.archetypes.get(&key) {
+            Some(&meta) => meta,
+            None => {
+                self.metas.push(BundleMeta::new::<B>());
+                self.archetypes.insert(key, self.metas.len() - 1);
+                self.metas.len() - 1
+            }
+        };
+        self.bundles.insert(B::type_id_(), meta);
+        meta
+    }
+
+    /// 原型代数,即[BundleMeta]的数量
+    ///
+    /// [BundleMeta]只增不减,所以可以用来判断有没有新的[BundleMeta]
+    pub(crate) fn archetype_generation(&self) -> usize {
+        self.metas.len()
+    }
+
+    /// 获取由[Composition]组成的[BundleMeta]的下标
+    ///
+    /// 如果[BundleMeta]不存在,就创建一个[BundleMeta::dynamic]
+    pub(crate) fn archetype_index(&mut self, composition: &Composition) -> usize {
+        let key = (&composition.ids[..], &composition.dynamic_ids[..]);
+        if let Some(&meta) = self.archetypes.get(&key) {
+            return meta;
+        }
+        // 组合的数量是有限的,所以直接泄露掉
+        let components_ids: &'static [TypeId] = Vec::leak(composition.ids.clone());
+        let components_names = Vec::leak(composition.names.clone());
+        let dynamic_ids: &'static [ComponentId] = Vec::leak(composition.dynamic_ids.clone());
+        self.metas.push(BundleMeta::dynamic(
+            components_ids,
+            components_names,
+            dynamic_ids,
+        ));
+        self.archetypes
+            .insert((components_ids, dynamic_ids), self.metas.len() - 1);
+        self.metas.len() - 1
+    }
+
+    /// 将[Components]放入meta对应的区块
+    pub(crate) fn insert_components(&mut self, meta: usize, mut cs: Components) -> Entity {
+        for idx in 0..self.metas[meta].chunks.len() {
+            let cid = self.metas[meta].chunks[idx];
+            match self.chunks[cid].insert_components(cs) {
+                Ok(entity) => return entity,
+                Err(back) => cs = back,
+            }
+        }
+        self.new_chunk(meta).insert_components(cs).unwrap()
+    }
+
+    /// 找到[Entity]对应的[Components]实际所在的位置
+    ///
+    /// [Entity]失效时返回[None]
+    pub(crate) fn locate(&self, entity: Entity) -> Option<Entity> {
+        let chunk = self.chunks.get(entity.chunk_index())?;
+        if !chunk.alive(entity)? {
+            return None;
+        }
+        Some(chunk.forwarded(entity.index_in_chunk()).unwrap_or(entity))
+    }
+
+    /// 获取[Entity]上类型为C的[Component]的不可变引用
+    pub(crate) fn get<C: Component>(&self, entity: Entity) -> Option<&C> {
+        let location = self.locate(entity)?;
+        let chunk = &self.chunks[location.chunk_index()];
+        let mapping = self.metas[chunk.meta()]
+            .components_ids
+            .iter()
+            .position(|id| *id == C::type_id_())?;
+        let components = unsafe { chunk.get(location.index_in_chunk()) };
+        components[mapping].downcast_ref()
+    }
+
+    /// 获取[Entity]上类型为C的[Component]的可变引用
+    pub(crate) fn get_mut<C: Component>(&mut self, entity: Entity) -> Option<&mut C> {
+        let location = self.locate(entity)?;
+        let chunk = &mut self.chunks[location.chunk_index()];
+        let mapping = self.metas[chunk.meta()]
+            .components_ids
+            .iter()
+            .position(|id| *id == C::type_id_())?;
+        let components = unsafe { chunk.get_mut(location.index_in_chunk()) };
+        self.changes.mark_changed(entity, C::type_id_());
+        components[mapping].downcast_mut()
+    }
+
+    /// 将[Bundle]中的[Component]加到[Entity]上
+    ///
+    /// + 已经存在的[Component]会被替换
+    /// + 否则[Entity]对应的[Components]会被移动到新的[BundleMeta]下,
+    ///   但是[Entity]本身不会失效
+    ///
+    /// 返回[Entity]是否有效
+    pub(crate) fn attach<B: Bundle>(&mut self, entity: Entity, b: B) -> bool {
+        let Some(location) = self.locate(entity) else {
+            return false;
+        };
+        let chunk = &mut self.chunks[location.chunk_index()];
+        let mut composition = Composition::new(&self.metas[chunk.meta()]);
+        let mut components = chunk.take(location.index_in_chunk());
+
+        for id in B::components_ids() {
+            self.changes.mark_changed(entity, *id);
+        }
+        let new_components = B::components_ids()
+            .iter()
+            .zip(B::components_names())
+            .zip(b.destory());
+        for ((id, name), component) in new_components {
+            match composition.ids.iter().position(|exist| exist == id) {
+                Some(idx) => components[idx] = component,
+                None => {
+                    // 静态的Component排在运行时的Component之前
+                    components.insert(composition.ids.len(), component);
+                    composition.ids.push(*id);
+                    composition.names.push(name);
+                }
+            }
+        }
+
+        self.relocate(entity, location, &composition, components);
+        self.index_changed(entity, B::components_ids());
+        true
+    }
+
+    /// 从[Entity]上拿走类型为C的[Component]
+    ///
+    /// [Entity]失效或者不存在C时返回[None]
+    pub(crate) fn detach<C: Component>(&mut self, entity: Entity) -> Option<C> {
+        let component = self.detach_by_id(entity, ComponentId::of::<C>())?;
+        component.downcast().ok().map(|c| *c)
+    }
+
+    /// 从[Entity]上拿走[ComponentId]对应的[Component]
+    ///
+    /// [Entity]失效或者不存在时返回[None]
+    pub(crate) fn detach_by_id(&mut self, entity: Entity, id: ComponentId) -> Option<Box<dyn Any>> {
+        let location = self.locate(entity)?;
+        let chunk = &mut self.chunks[location.chunk_index()];
+        let meta = &self.metas[chunk.meta()];
+        let idx = meta.position(id)?;
+        let mut composition = Composition::new(meta);
+
+        let mut components = chunk.take(location.index_in_chunk());
+        let component = components.remove(idx);
+        composition.remove(idx);
+
+        self.relocate(entity, location, &composition, components);
+        if let ComponentId::Static(id) = id {
+            self.changes.mark_changed(entity, id);
+            self.index_changed(entity, &[id]);
+        }
+        Some(component)
+    }
+
+    /// 把从location取出的[Components]放到[Composition]对应的[BundleMeta]下
+    ///
+    /// 并且记录移动,使entity仍然有效
+    pub(crate) fn relocate(
+        &mut self,
+        entity: Entity,
+        location: Entity,
+        composition: &Composition,
+        components: Components,
+    ) {
+        let meta = self.archetype_index(composition);
+        let old_chunk = location.chunk_index();
+        if self.chunks[old_chunk].meta() == meta {
+            self.chunks[old_chunk].put(location.index_in_chunk(), components);
+            return;
+        }
+
+        // entity原本的位置需要保留,用来记录移动
+        // 但是中途经过的位置可以直接释放
+        if location != entity {
+            self.chunks[old_chunk].remove(location);
+        }
+
+        let new_location = self.insert_components(meta, components);
+        self.chunks[new_location.chunk_index()].own(new_location.index_in_chunk(), entity);
+        self.chunks[entity.chunk_index()].forward(entity.index_in_chunk(), new_location);
+    }
+
+    /// 新的[Entity]出现时调用,为它们更新索引并记录变化
+    pub(crate) fn spawned(&mut self, entities: &[Entity]) {
+        self.changes.mark_all_changed(entities);
+        self.index_spawned(entities);
+    }
+
+    /// 直接从[World]中删除[Entity],不处理层级关系
+    ///
+    /// 和[Entity]有关的[Relation]和索引会被删除
+    pub(crate) fn despawn(&mut self, entity: Entity) -> bool {
+        let Some(location) = self.locate(entity) else {
+            return false;
+        };
+        let sources = self.relations.forget(entity);
+        self.indices.forget(entity);
+        self.changes.mark_all_changed(&[entity]);
+        if location != entity {
+            self.chunks[location.chunk_index()].remove(location);
+        }
+        let removed = self.chunks[entity.chunk_index()].remove(entity);
+        for (sync, source) in sources {
+            sync(self, source);
+        }
+        removed
+    }
 }
 
 #[cfg(feature = "system")]
 impl World {
-    #[cfg(not(feature = "async"))]
-    pub fn exec<M, S: InnerSystem<M>>(&self, mut s: S) {
-        s.run_once(s.build_args(self));
+    /// 立即执行一次[System],返回它的返回值
+    ///
+    /// 异步系统返回的是[Future],可以交给[block_on]执行
+    ///
+    /// 参数之间有冲突时会panic
+    ///
+    /// 独占系统会借用整个[World],所以需要`&mut self`
+    ///
+    /// ```compile_fail
+    /// fn exclusive(world: &trecs::World) {
+    ///     world.exec(|_: &mut trecs::World| {});
+    /// }
+    /// ```
+    ///
+    /// [Future]: std::future::Future
+    /// [block_on]: crate::system::block_on
+    pub fn exec<M, S: InnerSystem<M, In = ()>>(&mut self, mut s: S) -> S::Out {
+        let mut state = s.init().unwrap_or_else(|error| panic!("{error}"));
+        s.run_once((), s.build_args(self, &mut state))
     }
 
-    #[cfg(feature = "async")]
-    pub async fn exec<M, S: InnerSystem<M>>(&self, mut s: S) {
-        s.run_once(s.build_args(self)).await;
+    /// 设置处理[System]返回的错误的函数,默认会panic
+    ///
+    /// [handler]中有一些常用的处理方式
+    ///
+    /// [handler]: crate::error::handler
+    pub fn set_error_handler(&mut self, handler: fn(&mut World, SystemError)) -> &mut Self {
+        self.error_handler = handler;
+        self
     }
 
     /// 添加一个[System]
     ///
     /// 每次循环都会执行
-    pub fn add_system<M, S: InnerSystem<M>>(&mut self, system: S) -> &mut Self {
-        self.systems.push(System::new(system));
+    ///
+    /// 参数之间有冲突时会panic,使用[World::try_add_system]获得[TrecsError]
+    pub fn add_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        if let Err(error) = self.try_add_system(system) {
+            panic!("{error}");
+        }
         self
     }
 
+    /// 添加一个[System]
+    ///
+    /// 参数之间有冲突时返回[TrecsError],[System]不会被添加
+    pub fn try_add_system<M, S>(&mut self, system: S) -> Result<&mut Self, TrecsError>
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        self.systems.push(System::new(system)?);
+        Ok(self)
+    }
+
     /// 添加一个[System]
     ///
     /// 只会在刚开始循环时执行一次
-    pub fn add_startup_system<M, S: InnerSystem<M>>(&mut self, system: S) -> &mut Self {
-        self.startup_systems.push(System::new(system));
+    ///
+    /// 参数之间有冲突时会panic,使用[World::try_add_startup_system]获得[TrecsError]
+    pub fn add_startup_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        if let Err(error) = self.try_add_startup_system(system) {
+            panic!("{error}");
+        }
+        self
+    }
+
+    /// 添加一个只会在刚开始循环时执行一次的[System]
+    ///
+    /// 参数之间有冲突时返回[TrecsError],[System]不会被添加
+    pub fn try_add_startup_system<M, S>(&mut self, system: S) -> Result<&mut Self, TrecsError>
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        self.startup_systems.push(System::new(system)?);
+        Ok(self)
+    }
+
+    /// 添加一个[System]
+    ///
+    /// 只会在[World::run]和[World::run_until]返回前执行一次
+    ///
+    /// 参数之间有冲突时会panic,使用[World::try_add_shutdown_system]获得[TrecsError]
+    pub fn add_shutdown_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        if let Err(error) = self.try_add_shutdown_system(system) {
+            panic!("{error}");
+        }
+        self
+    }
+
+    /// 添加一个只会在循环结束时执行一次的[System]
+    ///
+    /// 参数之间有冲突时返回[TrecsError],[System]不会被添加
+    pub fn try_add_shutdown_system<M, S>(&mut self, system: S) -> Result<&mut Self, TrecsError>
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: SystemOutput + 'static,
+    {
+        self.shutdown_systems.push(System::new(system)?);
+        Ok(self)
+    }
+
+    /// 添加一个异步[System]
+    ///
+    /// 和同步的[System]按照添加的顺序一起执行,执行时会阻塞直到完成
+    ///
+    /// 参数之间有冲突时会panic,使用[World::try_add_async_system]获得[TrecsError]
+    #[cfg(feature = "async")]
+    pub fn add_async_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: Future + 'static,
+        <S::Out as Future>::Output: SystemOutput,
+    {
+        if let Err(error) = self.try_add_async_system(system) {
+            panic!("{error}");
+        }
+        self
+    }
+
+    /// 添加一个异步[System]
+    ///
+    /// 参数之间有冲突时返回[TrecsError],[System]不会被添加
+    #[cfg(feature = "async")]
+    pub fn try_add_async_system<M, S>(&mut self, system: S) -> Result<&mut Self, TrecsError>
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: Future + 'static,
+        <S::Out as Future>::Output: SystemOutput,
+    {
+        self.systems.push(System::new_async(system)?);
+        Ok(self)
+    }
+
+    /// 添加一个只会在刚开始循环时执行一次的异步[System]
+    ///
+    /// 参数之间有冲突时会panic,使用[World::try_add_async_startup_system]获得[TrecsError]
+    #[cfg(feature = "async")]
+    pub fn add_async_startup_system<M, S>(&mut self, system: S) -> &mut Self
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: Future + 'static,
+        <S::Out as Future>::Output: SystemOutput,
+    {
+        if let Err(error) = self.try_add_async_startup_system(system) {
+            panic!("{error}");
+        }
         self
     }
 
-    /// 进入一个死循环,直到线程终结
+    /// 添加一个只会在刚开始循环时执行一次的异步[System]
+    ///
+    /// 参数之间有冲突时返回[TrecsError],[System]不会被添加
+    #[cfg(feature = "async")]
+    pub fn try_add_async_startup_system<M, S>(
+        &mut self,
+        system: S,
+    ) -> Result<&mut Self, TrecsError>
+    where
+        S: InnerSystem<M, In = ()> + 'static,
+        S::Out: Future + 'static,
+        <S::Out as Future>::Output: SystemOutput,
+    {
+        self.startup_systems.push(System::new_async(system)?);
+        Ok(self)
+    }
+
+    /// 进入一个循环,直到有[System]请求退出
     ///
     /// 在执行一次所有被添加进startup_systems的[System]后
     ///
     /// 会进入循环,每次循环执行systems里的所有[System]
-    #[cfg(not(feature = "async"))]
+    ///
+    /// 存在[AppExit]资源时,在这次循环结束后执行shutdown_systems并返回
     pub fn run(&mut self) {
         self.run_until(|| false)
     }
-    #[cfg(feature = "async")]
-    pub async fn run(&mut self) {
-        self.run_until(|| false).await;
-    }
 
-    #[cfg(not(feature = "async"))]
+    /// 和[World::run]相同,until返回true时也会退出
     pub fn run_until<F>(&mut self, mut until: F)
     where
         F: FnMut() -> bool,
     {
         loop {
             if until() {
-                return;
+                break;
             }
 
             self.startup();
             self.run_once();
+
+            if self.exit_requested() {
+                break;
+            }
         }
+        self.shutdown();
     }
 
-    #[cfg(feature = "async")]
-    pub async fn run_until<F>(&mut self, mut until: F)
-    where
-        F: FnMut() -> bool,
-    {
-        loop {
-            if until() {
-                return;
-            }
+    /// 请求退出,和添加[AppExit]资源相同
+    ///
+    /// 会在这次循环结束时生效
+    pub fn exit(&mut self) -> &mut Self {
+        self.get_res().get_or_init(|| AppExit);
+        self
+    }
 
-            self.startup().await;
-            self.run_once().await;
-        }
+    /// 是否存在[AppExit]资源
+    pub fn exit_requested(&self) -> bool {
+        self.resources
+            .get(&TypeId::of::<AppExit>())
+            // 只检查是否存在,不会和Res冲突
+            .is_some_and(|res| unsafe { &*res.get() }.is_some())
     }
 
-    #[cfg(not(feature = "async"))]
-    pub fn startup(&mut self) -> &mut Self {
-        while let Some(mut stsys) = self.startup_systems.pop() {
-            stsys.run_once(self);
+    /// 执行所有shutdown_systems,然后移除[AppExit]资源
+    ///
+    /// shutdown_systems会被保留,再次[World::run]时仍然会执行
+    pub fn shutdown(&mut self) -> &mut Self {
+        let mut systems = std::mem::take(&mut self.shutdown_systems);
+        for system in &mut systems {
+            if let Err(error) = system.run_once(self) {
+                (self.error_handler)(self, error);
+            }
         }
+        // 执行时新添加的System
+        systems.append(&mut self.shutdown_systems);
+        self.shutdown_systems = systems;
+        self.get_res::<AppExit>().take();
         self
     }
 
-    #[cfg(feature = "async")]
-    pub async fn startup(&mut self) -> &mut Self {
+    pub fn startup(&mut self) -> &mut Self {
         while let Some(mut stsys) = self.startup_systems.pop() {
-            stsys.run_once(self).await;
+            if let Err(error) = stsys.run_once(self) {
+                (self.error_handler)(self, error);
+            }
         }
         self
     }
 
     /// 执行一次所有system
-    #[cfg(not(feature = "async"))]
+    ///
+    /// 同步系统直接执行,异步系统会阻塞当前线程直到完成
+    ///
+    /// 执行时新添加的[System]会从下一次开始执行
+    ///
+    /// 最后轮询一次通过[World::spawn_async]添加的异步任务
+    ///
+    /// 在[System]或异步任务中调用时会panic
     pub fn run_once(&mut self) {
-        let this = unsafe {
-            // stable没下面的"cast_ref_to_mut" 所以需要下面的allow
-            #[allow(unknown_lints)]
-            // nightly版本会deny 所以这需要allow
-            #[allow(clippy::cast_ref_to_mut)]
-            &mut *(self as *const _ as *mut World)
-        };
-        for sys in &mut self.systems {
-            sys.run_once(this);
-        }
-    }
-    #[cfg(feature = "async")]
-    pub async fn run_once(&mut self) {
-        let this = unsafe {
-            // stable没下面的"cast_ref_to_mut" 所以需要下面的allow
-            #[allow(unknown_lints)]
-            // nightly版本会deny 所以这需要allow
-            #[allow(clippy::cast_ref_to_mut)]
-            &mut *(self as *const _ as *mut World)
-        };
-        for sys in &mut self.systems {
-            sys.run_once(this).await;
+        assert!(
+            !self.running,
+            "World::run_once cannot be called from inside a running system or async task"
+        );
+        self.maintain();
+        // 取出systems,独占的System可以在执行时添加System
+        let mut systems = std::mem::take(&mut self.systems);
+        self.running = true;
+        for sys in &mut systems {
+            if let Err(error) = sys.run_once(self) {
+                (self.error_handler)(self, error);
+            }
         }
+        self.running = false;
+        systems.append(&mut self.systems);
+        self.systems = systems;
+        #[cfg(feature = "async")]
+        self.poll_async_tasks();
     }
 }
 
@@ -208,20 +719,17 @@ fn rev_result<T, E>(result: Result<T, E>) -> Result<E, T> {
 
 impl Command for World {
     fn register<B: crate::bundle::Bundle>(&mut self) {
-        let bundle_id = B::type_id_();
-        self.metas
-            .entry(bundle_id)
-            .or_insert_with(|| BundleMeta::new::<B>());
+        self.meta_index::<B>();
     }
 
     fn spawn<B: crate::bundle::Bundle>(&mut self, b: B) -> crate::storage::Entity {
-        self.register::<B>();
-        let bundle_id = B::type_id_();
+        let meta_index = self.meta_index::<B>();
         let mut bundle = Some(b);
 
-        let meta = self.metas.get_mut(&bundle_id).unwrap();
+        let meta = &self.metas[meta_index];
 
-        meta.chunks
+        let entity = meta
+            .chunks
             .iter()
             .try_fold((), |_, &cid| {
                 // Result<(),Entity>
@@ -229,8 +737,10 @@ impl Command for World {
                 Ok(())
             })
             .err()
-            .unwrap_or_else(|| self.new_chunk::<B>().insert(bundle?).ok())
-            .unwrap()
+            .unwrap_or_else(|| self.new_chunk(meta_index).insert(bundle?).ok())
+            .unwrap();
+        self.spawned(&[entity]);
+        entity
     }
 
     fn spawn_many<B: crate::bundle::Bundle, I: IntoIterator<Item = B>>(
@@ -238,18 +748,12 @@ impl Command for World {
         i: I,
     ) -> Vec<Entity> {
         // 注册&&准备meta
-        self.register::<B>();
+        let meta_index = self.meta_index::<B>();
         // let meta = self.metas.get_mut(&B::type_id_()).unwrap();
 
         // 准备迭代器和返回
         let mut i = i.into_iter();
-        let mut chuns_iter = self
-            .metas
-            .get_mut(&B::type_id_())
-            .unwrap()
-            .chunks
-            .clone()
-            .into_iter();
+        let mut chuns_iter = self.metas[meta_index].chunks.clone().into_iter();
 
         let mut entities = vec![];
 
@@ -257,7 +761,10 @@ impl Command for World {
 
         loop {
             // 判空
-            let Some(_temp_bundle) = temp_bundle.take().or_else(|| i.next()) else {return  entities;};
+            let Some(_temp_bundle) = temp_bundle.take().or_else(|| i.next()) else {
+                self.spawned(&entities);
+                return entities;
+            };
             temp_bundle = Some(_temp_bundle);
 
             let temp_chunk = 'get_chunk: {
@@ -266,7 +773,7 @@ impl Command for World {
                         break 'get_chunk chunk;
                     }
                 }
-                self.new_chunk::<B>()
+                self.new_chunk(meta_index)
             };
 
             let eneity_iter = (0..temp_chunk.free()).filter_map(|_| {
@@ -315,10 +822,92 @@ impl Command for World {
     }
 
     fn remove(&mut self, entity: crate::storage::Entity) -> bool {
-        self.chunks
-            .get_mut(entity.index / CHUNK_SIZE)
-            .map(|chunk| chunk.remove(entity))
-            .unwrap_or(false)
+        if self.locate(entity).is_none() {
+            return false;
+        }
+        // 保持层级关系的一致: 从父节点中移除,子节点变为根节点
+        self.remove_parent(entity);
+        if let Some(children) = self.get::<Children>(entity).map(|c| c.to_vec()) {
+            for child in children {
+                self.detach::<Parent>(child);
+            }
+        }
+        self.despawn(entity)
+    }
+
+    fn set_parent(&mut self, child: Entity, parent: Entity) -> bool {
+        if self.locate(child).is_none() || self.locate(parent).is_none() {
+            return false;
+        }
+        // 不能让一个Entity成为自己的祖先
+        if child == parent || self.ancestors(parent).any(|ancestor| ancestor == child) {
+            return false;
+        }
+        if self.get::<Parent>(child).map(|p| p.get()) == Some(parent) {
+            return true;
+        }
+
+        self.remove_parent(child);
+        self.attach(child, Parent(parent));
+        match self.get_mut::<Children>(parent) {
+            Some(children) => children.0.push(child),
+            None => {
+                self.attach(parent, Children(vec![child]));
+            }
+        }
+        true
+    }
+
+    fn remove_parent(&mut self, child: Entity) -> Option<Entity> {
+        let parent = self.detach::<Parent>(child)?.get();
+        let children = self.get_mut::<Children>(parent)?;
+        children.0.retain(|&c| c != child);
+        if children.is_empty() {
+            self.detach::<Children>(parent);
+        }
+        Some(parent)
+    }
+
+    fn despawn_recursive(&mut self, entity: Entity) -> bool {
+        if self.locate(entity).is_none() {
+            return false;
+        }
+        self.remove_parent(entity);
+        let descendants = self.descendants(entity).collect::<Vec<_>>();
+        for descendant in descendants {
+            self.despawn(descendant);
+        }
+        self.despawn(entity)
+    }
+
+    fn relate<R: Relation>(&mut self, source: Entity, target: Entity) -> bool {
+        if self.locate(source).is_none() || self.locate(target).is_none() {
+            return false;
+        }
+        if self.relations.insert::<R>(source, target) {
+            Relations::sync::<R>(self, source);
+        }
+        true
+    }
+
+    fn unrelate<R: Relation>(&mut self, source: Entity, target: Entity) -> bool {
+        let removed = self.relations.remove::<R>(source, target);
+        if removed {
+            Relations::sync::<R>(self, source);
+        }
+        removed
+    }
+
+    fn targets<R: Relation>(&self, source: Entity) -> &[Entity] {
+        self.relations.targets::<R>(source)
+    }
+
+    fn sources<R: Relation>(&self, target: Entity) -> &[Entity] {
+        self.relations.sources::<R>(target)
+    }
+
+    fn pairs<R: Relation>(&self) -> Vec<(Entity, Entity)> {
+        self.relations.pairs::<R>()
     }
 
     fn fetch<F: crate::tools::WorldFetch>(&mut self, entity: Entity) -> Option<F::Item<'_>> {
@@ -328,14 +917,13 @@ impl Command for World {
         // };
 
         // 脱糖
-        if !self.alive(entity).unwrap_or(false) {
-            return None;
-        }
+        let location = self.locate(entity)?;
         unsafe {
-            let chunk = self.chunks.get(entity.chunk_index())?;
-            let components = chunk.get(entity.index_in_chunk());
-            let mapping_table = self.metas.get_mut(&chunk.bundle_id())?.fetch::<F>()?;
-            let item = F::build(components, mapping_table);
+            let chunk = self.chunks.get(location.chunk_index())?;
+            let components = chunk.get(location.index_in_chunk());
+            let mapping_table = self.metas.get(chunk.meta())?.fetch::<F>()?;
+            self.changes.mark_fetched::<F>(entity);
+            let item = F::build(components, &mapping_table);
             Some(item)
         }
     }
@@ -351,7 +939,7 @@ impl Drop for World {
         }
 
         // Drop组件
-        for (.., meta) in &self.metas {
+        for meta in &self.metas {
             meta.chunks
                 .iter()
                 .copied()
@@ -420,4 +1008,315 @@ mod tests {
         assert_eq!(world.chunks.len(), 2);
         assert_eq!(entity.index, CHUNK_SIZE);
     }
+
+    #[test]
+    fn attach_detach() {
+        let mut world = World::new();
+        let entity = world.spawn(1i32);
+        let other = world.spawn(2i32);
+
+        // 移动到(i32,u8)的原型,原来的Entity仍然有效
+        assert!(world.attach(entity, 3u8));
+        let location = world.locate(entity).unwrap();
+        assert_ne!(location, entity);
+        assert_eq!(
+            world.chunks[entity.chunk_index()].forwarded(entity.index_in_chunk()),
+            Some(location)
+        );
+        assert_eq!(world.get::<i32>(entity), Some(&1));
+        assert_eq!(world.get::<u8>(entity), Some(&3));
+        assert_eq!(world.chunks[entity.chunk_index()].count(), 1);
+        // 遍历时得到的也是原来的Entity
+        assert_eq!(
+            crate::iter::EIter::from(Query::<&u8>::new(&mut world).into_iter())
+                .map(|e| e.entity())
+                .collect::<Vec<_>>(),
+            vec![entity]
+        );
+
+        // 移动回i32的原型,不会再经过原来的位置
+        assert_eq!(world.detach::<u8>(entity), Some(3));
+        assert_eq!(world.get::<i32>(entity), Some(&1));
+        assert_eq!(world.get::<u8>(entity), None);
+        assert_eq!(world.chunks[location.chunk_index()].count(), 0);
+        assert_eq!(world.get::<i32>(other), Some(&2));
+    }
+
+    #[test]
+    fn forwarding_chain() {
+        let mut world = World::new();
+        let entity = world.spawn(1i32);
+        let mut locations = vec![];
+        world.attach(entity, 1u8);
+        locations.push(world.locate(entity).unwrap());
+        world.attach(entity, 1u16);
+        locations.push(world.locate(entity).unwrap());
+        world.attach(entity, 1u32);
+        locations.push(world.locate(entity).unwrap());
+
+        // 原来的位置直接指向最新的位置,中途经过的位置都被释放了
+        let last = *locations.last().unwrap();
+        assert_eq!(
+            world.chunks[entity.chunk_index()].forwarded(entity.index_in_chunk()),
+            Some(last)
+        );
+        for location in &locations[..locations.len() - 1] {
+            assert_eq!(world.chunks[location.chunk_index()].count(), 0);
+            assert_eq!(world.chunks[location.chunk_index()].alive(*location), Some(false));
+        }
+        assert_eq!(
+            world.fetch::<(&i32, &u8, &u16, &u32)>(entity),
+            Some((&1, &1, &1, &1))
+        );
+    }
+
+    #[test]
+    fn despawn_after_move() {
+        let mut world = World::new();
+        let entity = world.spawn(1i32);
+        world.attach(entity, 1u8);
+        let location = world.locate(entity).unwrap();
+
+        // 原来的位置和移动后的位置都会被释放
+        assert!(world.remove(entity));
+        assert_eq!(world.locate(entity), None);
+        assert_eq!(world.get::<i32>(entity), None);
+        assert_eq!(world.chunks[entity.chunk_index()].alive(entity), Some(false));
+        assert_eq!(world.chunks[location.chunk_index()].alive(location), Some(false));
+        assert_eq!(world.chunks[location.chunk_index()].count(), 0);
+        assert!(!world.remove(entity));
+
+        // 原来的位置可以被复用,新的Entity不会被当作移动过的
+        let reused = world.spawn_many(0..CHUNK_SIZE as i32).pop().unwrap();
+        assert_eq!(reused.index, entity.index);
+        assert_ne!(reused, entity);
+        assert_eq!(world.chunks[reused.chunk_index()].forwarded(reused.index_in_chunk()), None);
+        assert_eq!(world.get::<i32>(reused), Some(&(CHUNK_SIZE as i32 - 1)));
+    }
+
+    #[test]
+    fn query() {
+        let mut world = World::new();
+        world.spawn((1usize, 10i32));
+        world.spawn((20i32, 2usize));
+        world.spawn(3usize);
+
+        let mut items = Query::<(&mut usize, &i32)>::new(&mut world)
+            .into_iter()
+            .map(|(u, i)| {
+                *u += 1;
+                (*u, *i)
+            })
+            .collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items, vec![(2, 10), (3, 20)]);
+
+        // 同一个Component不能在一个WorldFetch中出现两次
+        assert_eq!(
+            Query::<(&usize, &usize)>::new(&mut world)
+                .into_iter()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn iter() {
+        let mut world = World::new();
+        let entities = world.spawn_many((0..CHUNK_SIZE as i32 + 10).map(|i| (i, 0u8)));
+        world.spawn_many(0..5usize);
+        world.spawn((-1, 1usize));
+        for entity in entities.iter().step_by(3) {
+            world.remove(*entity);
+        }
+        // 被移走的Entity也只会出现一次
+        world.attach(entities[1], 1usize);
+
+        let mut query = Query::<(&i32, &u8)>::new(&mut world);
+        let mut all = query.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        let expected = (0..CHUNK_SIZE as i32 + 10)
+            .filter(|i| i % 3 != 0)
+            .collect::<Vec<_>>();
+        assert_eq!(query.len(), expected.len());
+        assert_eq!(query.iter().len(), expected.len());
+
+        let mut rev = query.iter().rev().map(|(i, _)| *i).collect::<Vec<_>>();
+        rev.reverse();
+        assert_eq!(rev, all);
+        all.sort();
+        assert_eq!(all, expected);
+
+        // 从两端交替遍历
+        let mut iter = query.iter_mut();
+        let mut both = vec![];
+        while let Some((front, _)) = iter.next() {
+            both.push(*front);
+            assert_eq!(iter.len(), expected.len() - both.len());
+            if let Some((back, _)) = iter.next_back() {
+                both.push(*back);
+            }
+        }
+        both.sort();
+        assert_eq!(both, expected);
+
+        let mut eiter = Query::<&usize>::new(&mut world).into_eiter();
+        assert_eq!(eiter.len(), 7);
+        assert_eq!(eiter.next_back().unwrap().entity(), entities[1]);
+    }
+
+    #[test]
+    fn sort_and_group() {
+        let mut world = World::new();
+        let e1 = world.spawn((3i32, 1u8));
+        let e2 = world.spawn((1i32, 2u8));
+        let e3 = world.spawn((2i32, 1u8, true));
+        let e4 = world.spawn((1i32, 1u8));
+
+        let mut query = Query::<(&i32, &mut u8)>::new(&mut world);
+        let sorted = query
+            .iter_sorted_by_key(|(i, _)| **i)
+            .map(|e| e.entity())
+            .collect::<Vec<_>>();
+        // key相同时保持原来的顺序
+        assert_eq!(sorted, vec![e2, e4, e3, e1]);
+
+        for mut e in query.iter_sorted_by_key(|(i, _)| -**i) {
+            *e.1 += 10;
+        }
+        let mut groups = query.group_by::<u8>(|(_, u)| **u);
+        assert_eq!(groups.len(), 2);
+        let mut eleven = groups
+            .remove(&11)
+            .unwrap()
+            .iter()
+            .map(|e| e.entity())
+            .collect::<Vec<_>>();
+        let mut expected = vec![e1, e3, e4];
+        eleven.sort();
+        expected.sort();
+        assert_eq!(eleven, expected);
+        assert_eq!(*groups[&12][0].0, 1);
+    }
+
+    #[test]
+    fn combinations() {
+        let mut world = World::new();
+        world.spawn_many((1..=3i32).map(|i| (i, 0u8)));
+        world.spawn((4i32, 0u8, true));
+
+        let mut query = Query::<(&i32, &mut u8)>::new(&mut world);
+        let mut combinations = query.iter_combinations_mut::<2>();
+        while let Some([(a, ua), (b, ub)]) = combinations.fetch_next() {
+            assert_ne!(a, b);
+            *ua += 1;
+            *ub += 1;
+        }
+        // 每个结果和其他3个结果各组合一次
+        assert!(query.iter_mut().all(|(_, u)| *u == 3));
+
+        let query = Query::<&i32>::new(&mut world);
+        let mut sums = query
+            .iter_combinations::<3>()
+            .map(|[a, b, c]| a + b + c)
+            .collect::<Vec<_>>();
+        sums.sort();
+        assert_eq!(sums, vec![6, 7, 8, 9]);
+        assert_eq!(query.iter_combinations::<0>().count(), 0);
+        assert_eq!(query.iter_combinations::<5>().count(), 0);
+        assert_eq!(query.iter_combinations::<4>().count(), 1);
+    }
+
+    #[test]
+    fn single() {
+        let mut world = World::new();
+        world.spawn(1u8);
+        world.spawn_many([(1usize, 2i32), (2, 3)]);
+
+        assert_eq!(Query::<&u8>::new(&mut world).single(), Ok(&1));
+        *Query::<&mut u8>::new(&mut world).single_mut().unwrap() += 1;
+        assert_eq!(Query::<&u8>::new(&mut world).single(), Ok(&2));
+        assert_eq!(
+            Query::<&usize>::new(&mut world).single(),
+            Err(QuerySingleError::MultipleEntities(2))
+        );
+        assert_eq!(
+            Query::<&bool>::new(&mut world).single(),
+            Err(QuerySingleError::NoEntities)
+        );
+        assert!(Query::<&bool>::new(&mut world).is_empty());
+    }
+
+    #[test]
+    fn query_state() {
+        let mut world = World::new();
+        world.spawn(1usize);
+
+        let mut state = QueryState::<&usize>::new();
+        state.update(&world);
+        assert_eq!(state.matched.len(), 1);
+
+        // 只会检查新注册的BundleMeta
+        world.spawn((2usize, 1u8));
+        world.spawn(1u8);
+        state.update(&world);
+        assert_eq!(state.matched.len(), 2);
+        state.update(&world);
+        assert_eq!(state.matched.len(), 2);
+
+        // 结果缓存在BundleMeta中
+        let cached = |meta: &BundleMeta| meta.fetch_cache.borrow().len();
+        assert_eq!(world.metas.iter().map(cached).collect::<Vec<_>>(), [1, 1, 0]);
+        let mut filtered = QueryState::<&usize, crate::tools::Without<u8>>::new();
+        filtered.update(&world);
+        assert_eq!(filtered.matched.len(), 1);
+        assert!(world
+            .metas
+            .iter()
+            .all(|meta| meta.filter_cache.borrow().len() == 1));
+    }
+
+    #[cfg(feature = "system")]
+    #[test]
+    fn query_state_in_system() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        fn count(query: Query<&usize>) {
+            COUNT.store(query.into_iter().count(), Ordering::SeqCst);
+        }
+
+        let mut world = World::new();
+        world.spawn(1usize);
+        world.add_system(count);
+        world.run_once();
+        assert_eq!(COUNT.load(Ordering::SeqCst), 1);
+
+        world.spawn((2usize, 1u8));
+        world.spawn(3usize);
+        world.run_once();
+        assert_eq!(COUNT.load(Ordering::SeqCst), 3);
+    }
+
+    #[cfg(feature = "system")]
+    #[test]
+    fn exclusive_system() {
+        fn double(mut query: Query<&mut usize>) {
+            query.iter_mut().for_each(|value| *value *= 2);
+        }
+
+        let mut world = World::new();
+        world.add_system(|world: &mut World| {
+            world.spawn(1usize);
+            world.exec(double);
+        });
+        world.add_system(|world: &mut World| {
+            let sum = Query::<&usize>::new(world).into_iter().sum::<usize>();
+            world.get_res::<usize>().get_or_init(|| 0);
+            *world.get_res::<usize>().get_mut().unwrap() = sum;
+        });
+        world.run_once();
+        assert_eq!(world.get_res::<usize>().get(), Some(&2));
+        world.run_once();
+        assert_eq!(world.get_res::<usize>().get(), Some(&6));
+    }
 }
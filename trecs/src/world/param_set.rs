@@ -0,0 +1,157 @@
+use std::{marker::PhantomData, mem::MaybeUninit};
+
+use crate::{
+    error::TrecsError,
+    system::{state::SystemState, ReborrowParm, SystemParm},
+    World,
+};
+
+/// [FnSystem]的参数之一
+///
+/// 可以包含互相冲突的参数,比如`Query<&mut T>`和`Query<&T>`
+///
+/// 每次只能通过p0,p1等方法借出其中一个参数,由借用检查保证它们不会同时存在
+///
+/// 和[ParamSet]之外的参数仍然不能冲突
+///
+/// ```ignore
+/// fn follow(mut set: ParamSet<(Query<&mut Pos>, Query<(&Pos, &Target)>)>) {
+///     for pos in set.p0().iter_mut() {}
+///     for (pos, target) in set.p1().iter() {}
+/// }
+/// ```
+///
+/// [FnSystem]: crate
+pub struct ParamSet<'a, T> {
+    world: &'a World,
+    /// 所有参数的[SystemParm::State]组成的元组
+    state: *mut u8,
+    /// 创建第n个参数的函数
+    build: Builder,
+    _p: PhantomData<T>,
+}
+
+/// 从[World]和擦除了类型的状态创建第n个参数,写入到out中
+type Builder = unsafe fn(n: usize, world: &World, state: *mut u8, out: *mut u8);
+
+/// 参数组成的元组,按照下标创建其中一个参数
+trait BuildNth {
+    /// state是所有参数的[SystemParm::State]组成的元组
+    unsafe fn build_nth(n: usize, world: &World, state: *mut u8, out: *mut u8);
+}
+
+unsafe fn build_into<P: SystemParm>(world: &World, state: &mut P::State, out: *mut u8) {
+    (out as *mut P).write(P::build(world, state));
+}
+
+unsafe impl<T> ReborrowParm for ParamSet<'_, T> {
+    type Item<'w>
+        = ParamSet<'w, T>
+    where
+        Self: 'w;
+}
+
+macro_rules! impl_param_set {
+    ($($p:ident $t:ident $i:tt),*) => {
+        impl<'a, $($t: ReborrowParm),*> ParamSet<'a, ($($t,)*)> {
+            $(
+                /// 借出其中一个参数
+                pub fn $p(&mut self) -> $t::Item<'_> {
+                    let mut param = MaybeUninit::<$t::Item<'_>>::uninit();
+                    // ReborrowParm保证参数和它的Item只有生命周期不同
+                    // 借出的参数借用了self,所以同时只会存在一个
+                    unsafe {
+                        (self.build)($i, self.world, self.state, param.as_mut_ptr() as *mut u8);
+                        param.assume_init()
+                    }
+                }
+            )*
+        }
+
+        impl<$($t: SystemParm),*> BuildNth for ($($t,)*) {
+            unsafe fn build_nth(n: usize, world: &World, state: *mut u8, out: *mut u8) {
+                let state = &mut *(state as *mut ($($t::State,)*));
+                match n {
+                    $($i => build_into::<$t>(world, &mut state.$i, out),)*
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        impl<'a, $($t: SystemParm),*> SystemParm for ParamSet<'a, ($($t,)*)> {
+            type State = ($($t::State,)*);
+
+            unsafe fn build(world: &World, state: &mut Self::State) -> Self {
+                // 状态保存在System中,比ParamSet活得更久
+                ParamSet {
+                    world: std::mem::transmute::<&World, &'a World>(world),
+                    state: state as *mut Self::State as *mut u8,
+                    build: <($($t,)*)>::build_nth,
+                    _p: PhantomData,
+                }
+            }
+
+            fn init(state: &mut SystemState) -> Result<(), TrecsError> {
+                // 每个参数分别和ParamSet之外的参数检查冲突,参数之间不检查
+                let outer = state.clone();
+                $(
+                    let mut inner = outer.clone();
+                    $t::init(&mut inner)?;
+                    state.merge(inner);
+                )*
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_param_set!(p0 P0 0, p1 P1 1);
+impl_param_set!(p0 P0 0, p1 P1 1, p2 P2 2);
+impl_param_set!(p0 P0 0, p1 P1 1, p2 P2 2, p3 P3 3);
+impl_param_set!(p0 P0 0, p1 P1 1, p2 P2 2, p3 P3 3, p4 P4 4);
+impl_param_set!(p0 P0 0, p1 P1 1, p2 P2 2, p3 P3 3, p4 P4 4, p5 P5 5);
+impl_param_set!(p0 P0 0, p1 P1 1, p2 P2 2, p3 P3 3, p4 P4 4, p5 P5 5, p6 P6 6);
+impl_param_set!(p0 P0 0, p1 P1 1, p2 P2 2, p3 P3 3, p4 P4 4, p5 P5 5, p6 P6 6, p7 P7 7);
+
+#[cfg(test)]
+mod tests {
+    use super::ParamSet;
+    use crate::{error::TrecsError, tools::Command, world::Query, World};
+
+    #[test]
+    fn param_set() {
+        fn double(mut set: ParamSet<(Query<&mut usize>, Query<&u8>)>) {
+            for value in set.p0().iter_mut() {
+                *value *= 2;
+            }
+            assert_eq!(set.p1().iter().count(), 2);
+            for value in set.p0().iter_mut() {
+                assert_eq!(*value % 2, 0);
+            }
+        }
+
+        let mut world = World::new();
+        world.spawn((1usize, 1u8));
+        world.spawn((2usize, 2u8));
+        world.exec(double);
+        assert_eq!(
+            Query::<&usize>::new(&mut world).into_iter().sum::<usize>(),
+            6
+        );
+    }
+
+    #[test]
+    fn conflict_outside() {
+        fn conflict(_set: ParamSet<(Query<&mut usize>, Query<&usize>)>, _query: Query<&usize>) {}
+        assert_eq!(
+            World::new().try_add_system(conflict).err(),
+            Some(TrecsError::AliasConflict {
+                component: "usize",
+                first: "&mut usize",
+                first_mut: true,
+                second: "&usize",
+                second_mut: false,
+            })
+        );
+    }
+}
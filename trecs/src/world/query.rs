@@ -1,10 +1,16 @@
-use std::marker::PhantomData;
+use std::{borrow::Cow, collections::HashMap, hash::Hash, marker::PhantomData};
 
 #[cfg(feature = "system")]
-use crate::system::SystemParm;
 use crate::{
-    iter::{EIter, Iter},
-    tools::{WorldFetch, WorldFilter},
+    error::TrecsError,
+    system::{state::ArchetypeFilter, ReborrowParm, SystemParm},
+};
+use crate::{
+    bundle::Relation,
+    hierarchy::{Ancestors, Children, Descendants, Parent},
+    iter::{count, Combinations, EBundle, EIter, Iter, Matched},
+    storage::Entity,
+    tools::{Command, MappingTable, ReadOnlyFetch, WorldFetch, WorldFilter},
     world::World,
 };
 
@@ -20,47 +26,348 @@ use crate::bundle::Components;
 #[derive(Clone)]
 pub struct Query<'a, F: WorldFetch, Q: WorldFilter = ()> {
     world: &'a World,
-    _p: PhantomData<(F, Q)>,
+    state: Cow<'a, QueryState<F, Q>>,
 }
 
 impl<'a, F: WorldFetch, Q: WorldFilter> Query<'a, F, Q> {
     pub fn new(world: &mut World) -> Query<'_, F, Q> {
+        let mut state = QueryState::new();
+        state.update(world);
+        state.mark_written(world);
         Query {
             world,
-            _p: PhantomData,
+            state: Cow::Owned(state),
+        }
+    }
+
+    fn matched(self) -> Cow<'a, [(usize, MappingTable)]> {
+        match self.state {
+            Cow::Borrowed(state) => Cow::Borrowed(&state.matched),
+            Cow::Owned(state) => Cow::Owned(state.matched),
         }
     }
 
     pub fn into_eiter(self) -> EIter<'a, F> {
-        unsafe {
-            #[allow(mutable_transmutes)]
-            EIter::new::<Q>(std::mem::transmute(self.world))
+        Iter::from_matched(self.world, self.matched()).into()
+    }
+
+    /// 遍历所有结果
+    ///
+    /// 只读的[Query]才能通过共享引用遍历
+    pub fn iter(&self) -> Iter<'_, F>
+    where
+        F: ReadOnlyFetch,
+    {
+        Iter::from_matched(self.world, Cow::Borrowed(&self.state.matched))
+    }
+
+    /// 遍历所有结果
+    pub fn iter_mut(&mut self) -> Iter<'_, F> {
+        Iter::from_matched(self.world, Cow::Borrowed(&self.state.matched))
+    }
+
+    /// 获取[Entity]对应的结果
+    ///
+    /// [Entity]失效或者不满足[Query]时返回[None]
+    pub fn get(&self, entity: Entity) -> Option<F::Item<'_>>
+    where
+        F: ReadOnlyFetch,
+    {
+        unsafe { self.get_unchecked(entity) }
+    }
+
+    /// 获取[Entity]对应的结果
+    ///
+    /// [Entity]失效或者不满足[Query]时返回[None]
+    pub fn get_mut(&mut self, entity: Entity) -> Option<F::Item<'_>> {
+        unsafe { self.get_unchecked(entity) }
+    }
+
+    /// 通过[Relation]R指向target的所有结果
+    ///
+    /// 使用[Relations]的反向索引,不会遍历所有结果
+    ///
+    /// [Relations]: crate::bundle
+    pub fn iter_targeting<R: Relation>(
+        &self,
+        target: Entity,
+    ) -> impl Iterator<Item = F::Item<'_>> + '_
+    where
+        F: ReadOnlyFetch,
+    {
+        // 遍历时Commands可能会修改Relations,所以先复制一份
+        let sources = self.world.sources::<R>(target).to_vec();
+        sources
+            .into_iter()
+            .filter_map(|source| unsafe { self.get_unchecked(source) })
+    }
+
+    /// 通过[Relation]R指向target的所有结果
+    ///
+    /// 使用[Relations]的反向索引,不会遍历所有结果
+    ///
+    /// [Relations]: crate::bundle
+    pub fn iter_targeting_mut<R: Relation>(
+        &mut self,
+        target: Entity,
+    ) -> impl Iterator<Item = F::Item<'_>> + '_ {
+        // 每个source只会出现一次,所以结果之间不会产生别名
+        let sources = self.world.sources::<R>(target).to_vec();
+        sources
+            .into_iter()
+            .filter_map(|source| unsafe { self.get_unchecked(source) })
+    }
+
+    /// # Safety
+    ///
+    /// 调用者需要保证返回的结果不会和其他可变的结果产生别名
+    unsafe fn get_unchecked(&self, entity: Entity) -> Option<F::Item<'_>> {
+        let location = self.world.locate(entity)?;
+        let chunk = &self.world.chunks[location.chunk_index()];
+        let (_, mapping_table) = self
+            .state
+            .matched
+            .iter()
+            .find(|(meta, _)| *meta == chunk.meta())?;
+        Some(F::build(
+            chunk.get(location.index_in_chunk()),
+            mapping_table,
+        ))
+    }
+
+    /// 结果的数量
+    ///
+    /// 只会统计每个[Chunk]中的数量,不会遍历
+    ///
+    /// [Chunk]: crate
+    pub fn len(&self) -> usize {
+        count(self.world, &self.state.matched)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 按照key排序后遍历所有结果
+    ///
+    /// 只会排序[EBundle],不会复制[Component]
+    ///
+    /// 排序是稳定的,key相同的结果保持原来的顺序
+    ///
+    /// [Component]: crate
+    pub fn iter_sorted_by_key<K: Ord>(
+        &mut self,
+        mut key: impl FnMut(&F::Item<'_>) -> K,
+    ) -> std::vec::IntoIter<EBundle<'_, F>> {
+        let mut items = EIter::from(self.iter_mut()).collect::<Vec<_>>();
+        items.sort_by_cached_key(|item| key(item));
+        items.into_iter()
+    }
+
+    /// 按照key将所有结果分组
+    ///
+    /// 每一组中的结果保持遍历时的顺序
+    ///
+    /// ```ignore
+    /// let teams = query.group_by::<Team>(|(_, team)| **team);
+    /// ```
+    pub fn group_by<K: Hash + Eq>(
+        &mut self,
+        mut key: impl FnMut(&F::Item<'_>) -> K,
+    ) -> HashMap<K, Vec<EBundle<'_, F>>> {
+        EIter::from(self.iter_mut()).fold(HashMap::new(), |mut groups, item| {
+            groups.entry(key(&item)).or_default().push(item);
+            groups
+        })
+    }
+
+    /// 遍历所有K个不同结果的组合
+    ///
+    /// ```ignore
+    /// for [(pos1, r1), (pos2, r2)] in query.iter_combinations::<2>() {}
+    /// ```
+    pub fn iter_combinations<const K: usize>(&self) -> Combinations<'_, F, K>
+    where
+        F: ReadOnlyFetch,
+    {
+        Combinations::new(self.iter())
+    }
+
+    /// 遍历所有K个不同结果的组合
+    ///
+    /// ```ignore
+    /// let mut combinations = query.iter_combinations_mut::<2>();
+    /// while let Some([a, b]) = combinations.fetch_next() {}
+    /// ```
+    pub fn iter_combinations_mut<const K: usize>(&mut self) -> Combinations<'_, F, K> {
+        Combinations::new(self.iter_mut())
+    }
+
+    /// 唯一的结果
+    ///
+    /// 没有结果或者有多个结果时返回[QuerySingleError]
+    pub fn single(&self) -> Result<F::Item<'_>, QuerySingleError>
+    where
+        F: ReadOnlyFetch,
+    {
+        single(self.iter())
+    }
+
+    /// 唯一的结果
+    ///
+    /// 没有结果或者有多个结果时返回[QuerySingleError]
+    pub fn single_mut(&mut self) -> Result<F::Item<'_>, QuerySingleError> {
+        single(self.iter_mut())
+    }
+}
+
+/// 只有读取[Parent]的[Query]才能遍历祖先,
+/// 这样对[Parent]的读取会被计入别名检查
+impl<'a> Query<'a, &Parent> {
+    /// 遍历[Entity]的所有祖先
+    pub fn ancestors(&self, entity: Entity) -> Ancestors<'a> {
+        self.world.ancestors(entity)
+    }
+}
+
+/// 只有读取[Children]的[Query]才能遍历后代,
+/// 这样对[Children]的读取会被计入别名检查
+impl<'a> Query<'a, &Children> {
+    /// 遍历[Entity]的所有后代
+    pub fn descendants(&self, entity: Entity) -> Descendants<'a> {
+        self.world.descendants(entity)
+    }
+}
+
+fn single<I: ExactSizeIterator>(mut iter: I) -> Result<I::Item, QuerySingleError> {
+    match iter.len() {
+        0 => Err(QuerySingleError::NoEntities),
+        1 => Ok(iter.next().unwrap()),
+        n => Err(QuerySingleError::MultipleEntities(n)),
+    }
+}
+
+/// [Query::single]和[Query::single_mut]的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuerySingleError {
+    /// 没有结果
+    NoEntities,
+    /// 有多个结果,以及结果的数量
+    MultipleEntities(usize),
+}
+
+impl std::fmt::Display for QuerySingleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoEntities => write!(f, "query matched no entities, expected exactly one"),
+            Self::MultipleEntities(n) => {
+                write!(f, "query matched {n} entities, expected exactly one")
+            }
         }
     }
 }
 
+impl std::error::Error for QuerySingleError {}
+
 impl<'a, F: WorldFetch + 'a, Q: WorldFilter> IntoIterator for Query<'a, F, Q> {
     type Item = F::Item<'a>;
 
     type IntoIter = Iter<'a, F>;
 
     fn into_iter(self) -> Self::IntoIter {
-        unsafe {
-            #[allow(mutable_transmutes)]
-            Iter::new::<Q>(std::mem::transmute(self.world))
+        Iter::from_matched(self.world, self.matched())
+    }
+}
+
+/// [Query]的状态
+///
+/// 记录匹配的[BundleMeta],以及已经检查过的[BundleMeta]的数量
+///
+/// 作为[System]的参数时保存在[System]中,每次运行只会检查新注册的[BundleMeta]
+///
+/// [BundleMeta]: crate
+/// [System]: crate
+pub struct QueryState<F: WorldFetch, Q: WorldFilter = ()> {
+    pub(crate) matched: Matched,
+    /// 上一次检查时[World]的原型代数
+    generation: usize,
+    _p: PhantomData<fn() -> (F, Q)>,
+}
+
+impl<F: WorldFetch, Q: WorldFilter> QueryState<F, Q> {
+    pub fn new() -> Self {
+        Self {
+            matched: vec![],
+            generation: 0,
+            _p: PhantomData,
+        }
+    }
+
+    /// 检查上一次之后新注册的[BundleMeta]
+    ///
+    /// 结果会缓存在[BundleMeta]中,同样的[Query]不需要重新计算
+    ///
+    /// [BundleMeta]: crate
+    pub fn update(&mut self, world: &World) {
+        for (idx, meta) in world.metas.iter().enumerate().skip(self.generation) {
+            if !Q::filter_by_meta(meta) {
+                continue;
+            }
+            if let Some(mapping_table) = meta.fetch::<F>() {
+                self.matched.push((idx, mapping_table));
+            }
+        }
+        self.generation = world.archetype_generation();
+    }
+
+    /// 可写的[Query]可能会修改所有匹配的[Entity],记录下这些原型
+    fn mark_written(&self, world: &World) {
+        world
+            .changes
+            .mark_written::<F>(self.matched.iter().map(|(meta, _)| *meta));
+    }
+}
+
+impl<F: WorldFetch, Q: WorldFilter> Default for QueryState<F, Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: WorldFetch, Q: WorldFilter> Clone for QueryState<F, Q> {
+    fn clone(&self) -> Self {
+        Self {
+            matched: self.matched.clone(),
+            generation: self.generation,
+            _p: PhantomData,
         }
     }
 }
 
 #[cfg(feature = "system")]
-impl<F: WorldFetch, Q: WorldFilter> SystemParm for Query<'_, F, Q> {
-    unsafe fn build(world: &World) -> Self {
-        #[allow(mutable_transmutes)]
-        let world: &mut World = std::mem::transmute(world);
-        Query::<'_, F, Q>::new(world)
+unsafe impl<F: WorldFetch, Q: WorldFilter> ReborrowParm for Query<'_, F, Q> {
+    type Item<'w> = Query<'w, F, Q> where Self: 'w;
+}
+
+#[cfg(feature = "system")]
+impl<'a, F: WorldFetch + 'static, Q: WorldFilter> SystemParm for Query<'a, F, Q> {
+    type State = QueryState<F, Q>;
+
+    unsafe fn build(world: &World, state: &mut Self::State) -> Self {
+        state.update(world);
+        state.mark_written(world);
+        // 状态保存在System中,比Query活得更久
+        let state: &QueryState<F, Q> = &*(state as *const _);
+        Query {
+            world: std::mem::transmute::<&World, &'a World>(world),
+            state: Cow::Borrowed(state),
+        }
     }
 
-    fn init(state: &mut crate::system::state::SystemState) {
+    fn init(state: &mut crate::system::state::SystemState) -> Result<(), TrecsError> {
         F::alias_conflict(&mut state.alias_map);
+        let mut filter = ArchetypeFilter::default();
+        Q::archetype_filter(&mut filter);
+        state.alias_map.commit(filter)
     }
 }
@@ -72,21 +72,37 @@ impl<'a, T: 'static> Res<'a, T> {
 }
 
 #[cfg(feature = "system")]
-use crate::{system::SystemParm, world::World};
+use crate::{
+    error::TrecsError,
+    system::{ReborrowParm, SystemParm},
+    world::World,
+};
+
+#[cfg(feature = "system")]
+unsafe impl<T: 'static> ReborrowParm for Res<'_, T> {
+    type Item<'w> = Res<'w, T> where Self: 'w;
+}
 
 #[cfg(feature = "system")]
 impl<'a, T: 'static> SystemParm for Res<'a, T> {
-    unsafe fn build(world: &World) -> Self {
+    type State = ();
+
+    unsafe fn build(world: &World, _state: &mut Self::State) -> Self {
         #[allow(mutable_transmutes)]
         let world: &mut World = std::mem::transmute(world);
         std::mem::transmute(world.get_res::<T>())
     }
 
-    fn init(state: &mut crate::system::state::SystemState) {
-        if state.resources || state.res.contains(&TypeId::of::<T>()) {
-            panic!("Res不可和Resources或者重复的Res共存")
+    fn init(state: &mut crate::system::state::SystemState) -> Result<(), TrecsError> {
+        if state.resources {
+            return Err(TrecsError::ResourcesConflict { param: "Res" });
+        }
+        if !state.res.insert(TypeId::of::<T>()) {
+            return Err(TrecsError::DuplicateRes {
+                resource: std::any::type_name::<T>(),
+            });
         }
-        state.res.insert(TypeId::of::<T>());
+        Ok(())
     }
 }
 
@@ -130,9 +146,16 @@ impl<'a> ResManager for Resources<'a> {
     }
 }
 
+#[cfg(feature = "system")]
+unsafe impl ReborrowParm for Resources<'_> {
+    type Item<'w> = Resources<'w> where Self: 'w;
+}
+
 #[cfg(feature = "system")]
 impl SystemParm for Resources<'_> {
-    unsafe fn build(world: &World) -> Self {
+    type State = ();
+
+    unsafe fn build(world: &World, _state: &mut Self::State) -> Self {
         #[allow(mutable_transmutes)]
         let world: &mut World = std::mem::transmute(world);
         Self {
@@ -141,12 +164,16 @@ impl SystemParm for Resources<'_> {
         }
     }
 
-    fn init(state: &mut crate::system::state::SystemState) {
+    fn init(state: &mut crate::system::state::SystemState) -> Result<(), TrecsError> {
         // 理论上因为UnsafeCell会自己在运行时painc
         // 但是还是提前制止吧?
-        if state.resources || !state.res.is_empty() {
-            panic!("Resources不可和其他Resources或者任何Res共存")
+        if state.resources {
+            return Err(TrecsError::ResourcesConflict { param: "Resources" });
+        }
+        if !state.res.is_empty() {
+            return Err(TrecsError::ResourcesConflict { param: "Res" });
         }
         state.resources = true;
+        Ok(())
     }
 }